@@ -0,0 +1,118 @@
+// Sends console commands to an app started with --control <port>. With a command on the command
+// line, it runs just that one and exits with 1 if it failed. Otherwise it reads commands from
+// stdin, one per line, so a whole script can be piped into it.
+//
+//     cargo run --bin control -- camera_pose
+//     cargo run --bin control -- --port 7000 < commands.txt
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+use serde_json::{json, Value};
+
+/// The same as DEFAULT_PORT in control_server.rs.
+const DEFAULT_PORT: u16 = 7878;
+
+const USAGE: &str = "usage: control [--port <port>] [command]";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut port = DEFAULT_PORT;
+    let mut command = Vec::new();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("--port needs a value\n{}", USAGE))?;
+                port = value
+                    .parse()
+                    .map_err(|_| format!("expected a port number, got {}", value))?;
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => command.push(arg),
+        }
+    }
+
+    let stream = TcpStream::connect(("127.0.0.1", port))
+        .map_err(|err| format!("could not connect to port {}, {}", port, err))?;
+    let mut client = Client {
+        reader: BufReader::new(stream.try_clone().map_err(|err| err.to_string())?),
+        stream,
+        next_id: 0,
+    };
+
+    if !command.is_empty() {
+        let ok = client.send(&command.join(" "))?;
+
+        if !ok {
+            std::process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        client.send(line)?;
+    }
+
+    Ok(())
+}
+
+struct Client {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+    next_id: u64,
+}
+
+impl Client {
+    /// Sends a command and prints the reply. Returns whether the command worked.
+    fn send(&mut self, command: &str) -> Result<bool, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        writeln!(self.stream, "{}", json!({ "id": id, "command": command }))
+            .map_err(|err| format!("could not send {}, {}", command, err))?;
+
+        let mut line = String::new();
+
+        match self.reader.read_line(&mut line) {
+            Ok(0) => return Err("the app closed the connection".to_string()),
+            Ok(_) => (),
+            Err(err) => return Err(format!("could not read the reply, {}", err)),
+        }
+
+        let reply: Value = serde_json::from_str(&line)
+            .map_err(|err| format!("could not read the reply {}, {}", line.trim(), err))?;
+
+        if reply["ok"].as_bool() == Some(true) {
+            for output in reply["output"].as_array().into_iter().flatten() {
+                println!("{}", output.as_str().unwrap_or_default());
+            }
+
+            Ok(true)
+        } else {
+            eprintln!("error: {}", reply["error"].as_str().unwrap_or_default());
+
+            Ok(false)
+        }
+    }
+}
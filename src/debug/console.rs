@@ -0,0 +1,481 @@
+// The developer console. Shift+C or ` opens it, and Escape or ` closes it again. Commands typed
+// into it are sent as Command events, and anything sent as a ConsoleMessage is shown in its
+// scrollback and printed to stdout.
+
+use std::collections::VecDeque;
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::ElementState;
+use bevy::prelude::KeyCode::*;
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+
+use crate::debug::registry::CommandRegistry;
+use crate::debug::Command;
+use crate::input::KeyboardInputState;
+use crate::rendering::text::{Hud, TextStyle, GLYPH_WIDTH};
+
+/// How many lines of the scrollback are shown at once.
+const VISIBLE_LINES: usize = 16;
+const MAX_SCROLLBACK: usize = 500;
+const MAX_HISTORY: usize = 100;
+
+/// Where the top left of the console is, in pixels.
+const CONSOLE_POS: [f32; 2] = [8.0, 8.0];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.35, 1.0];
+const ECHO_COLOR: [f32; 4] = [0.6, 0.8, 1.0, 1.0];
+
+/// A line of output for the console. Send these instead of printing the result of a command, so
+/// that it can be seen without looking at the terminal.
+#[derive(Debug, Clone)]
+pub struct ConsoleMessage {
+    pub text: String,
+    pub kind: MessageKind,
+    /// The control server request this is the result of, see Command::control_request.
+    pub control_request: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Info,
+    Error,
+    /// A command that was typed in, repeated back.
+    Echo,
+}
+
+impl ConsoleMessage {
+    pub fn info(text: impl Into<String>) -> Self {
+        ConsoleMessage {
+            text: text.into(),
+            kind: MessageKind::Info,
+            control_request: None,
+        }
+    }
+
+    pub fn error(text: impl Into<String>) -> Self {
+        ConsoleMessage {
+            text: text.into(),
+            kind: MessageKind::Error,
+            control_request: None,
+        }
+    }
+
+    /// Marks this as the result of a control server request, so that it is sent back in the reply.
+    pub fn for_request(self, control_request: Option<u64>) -> Self {
+        ConsoleMessage {
+            control_request,
+            ..self
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Console {
+    input: String,
+    /// Where the cursor is in input, in chars rather than bytes.
+    cursor: usize,
+    history: Vec<String>,
+    /// Which line of history is in input, while going back through it with up and down.
+    history_index: Option<usize>,
+    scrollback: VecDeque<ConsoleMessage>,
+    /// How many lines up from the newest the scrollback has been scrolled.
+    scroll: usize,
+}
+
+impl Console {
+    /// Adds a message to the scrollback, one entry per line.
+    pub fn log(&mut self, message: ConsoleMessage) {
+        for line in message.text.lines() {
+            if self.scrollback.len() == MAX_SCROLLBACK {
+                self.scrollback.pop_front();
+            }
+
+            self.scrollback.push_back(ConsoleMessage {
+                text: line.to_string(),
+                kind: message.kind,
+                control_request: message.control_request,
+            });
+        }
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.input
+            .char_indices()
+            .nth(char_index)
+            .map_or(self.input.len(), |(index, _)| index)
+    }
+
+    fn char_count(&self) -> usize {
+        self.input.chars().count()
+    }
+
+    fn insert(&mut self, c: char) {
+        let index = self.byte_index(self.cursor);
+
+        self.input.insert(index, c);
+        self.cursor += 1;
+    }
+
+    fn set_input(&mut self, text: String) {
+        self.input = text;
+        self.cursor = self.char_count();
+    }
+
+    /// Clears the input, and returns what it was if there was anything in it.
+    fn take_input(&mut self) -> Option<String> {
+        let line = self.input.trim().to_string();
+
+        self.set_input(String::new());
+        self.history_index = None;
+        self.scroll = 0;
+
+        if line.is_empty() {
+            return None;
+        }
+
+        if self.history.last() != Some(&line) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.remove(0);
+            }
+
+            self.history.push(line.clone());
+        }
+
+        Some(line)
+    }
+
+    fn history_back(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        self.history_index = Some(index);
+        self.set_input(self.history[index].clone());
+    }
+
+    fn history_forward(&mut self) {
+        match self.history_index {
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+                self.set_input(self.history[index + 1].clone());
+            }
+            Some(_) => {
+                self.history_index = None;
+                self.set_input(String::new());
+            }
+            None => (),
+        }
+    }
+
+    /// Completes the word before the cursor as far as it can. If there is more than one way to
+    /// finish it, they are all listed in the scrollback.
+    fn complete(&mut self, registry: &CommandRegistry) {
+        let before_cursor: String = self.input.chars().take(self.cursor).collect();
+        let words: Vec<&str> = before_cursor.split_whitespace().collect();
+
+        let (words_before, partial) = match words.split_last() {
+            Some((last, rest)) if !before_cursor.ends_with(char::is_whitespace) => (rest, *last),
+            _ => (&words[..], ""),
+        };
+
+        let candidates = registry.complete(words_before, partial);
+
+        let completion = match candidates.as_slice() {
+            [] => return,
+            [only] => format!("{} ", only),
+            [first, rest @ ..] => {
+                self.log(ConsoleMessage::info(candidates.join("  ")));
+
+                let common = rest.iter().fold(first.len(), |len, candidate| {
+                    first
+                        .bytes()
+                        .zip(candidate.bytes())
+                        .take(len)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+
+                first[..common].to_string()
+            }
+        };
+
+        for c in completion.chars().skip(partial.chars().count()) {
+            self.insert(c);
+        }
+    }
+
+    /// Handles a key that edits the input or moves around in it. Returns true if it was Return.
+    fn edit(&mut self, key: KeyCode) -> bool {
+        match key {
+            Return | NumpadEnter => return true,
+            Back if self.cursor > 0 => {
+                self.cursor -= 1;
+                let index = self.byte_index(self.cursor);
+                self.input.remove(index);
+            }
+            Delete if self.cursor < self.char_count() => {
+                let index = self.byte_index(self.cursor);
+                self.input.remove(index);
+            }
+            Left => self.cursor = self.cursor.saturating_sub(1),
+            Right => self.cursor = (self.cursor + 1).min(self.char_count()),
+            Home => self.cursor = 0,
+            End => self.cursor = self.char_count(),
+            Up => self.history_back(),
+            Down => self.history_forward(),
+            PageUp => {
+                let max_scroll = self.scrollback.len().saturating_sub(VISIBLE_LINES);
+                self.scroll = (self.scroll + VISIBLE_LINES / 2).min(max_scroll);
+            }
+            PageDown => self.scroll = self.scroll.saturating_sub(VISIBLE_LINES / 2),
+            _ => (),
+        }
+
+        false
+    }
+}
+
+pub fn console_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut key_events: EventReader<KeyboardInput>,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    mut input_state: ResMut<KeyboardInputState>,
+    mut console: ResMut<Console>,
+    mut command_events: EventWriter<Command>,
+    registry: Res<CommandRegistry>,
+) {
+    // both are read every frame, even when closed, so that the key that opens the console isn't
+    // typed into it. key events are used instead of just_pressed, so that held keys repeat.
+    let typed: Vec<char> = received_characters.iter().map(|event| event.char).collect();
+    let pressed_keys: Vec<KeyCode> = key_events
+        .iter()
+        .filter(|event| event.state == ElementState::Pressed)
+        .filter_map(|event| event.key_code)
+        .collect();
+
+    let is_open = *input_state == KeyboardInputState::Commands;
+    let toggle_pressed = keyboard_input.just_pressed(Grave)
+        || (!is_open && keyboard_input.pressed(LShift) && keyboard_input.just_pressed(C));
+
+    if !is_open {
+        if toggle_pressed {
+            *input_state = KeyboardInputState::Commands;
+        }
+
+        return;
+    }
+
+    if toggle_pressed || keyboard_input.just_pressed(Escape) {
+        *input_state = KeyboardInputState::default();
+        return;
+    }
+
+    for key in pressed_keys {
+        if key == Tab {
+            console.complete(&registry);
+            continue;
+        }
+
+        if !console.edit(key) {
+            continue;
+        }
+
+        if let Some(line) = console.take_input() {
+            console.log(ConsoleMessage {
+                text: format!("> {}", line),
+                kind: MessageKind::Echo,
+                control_request: None,
+            });
+
+            // commands are checked here, so that the systems that handle them only have to report
+            // errors that depend on the state of the world.
+            if let Some(command) = Command::from_line(&line) {
+                match registry.validate(&command) {
+                    Ok(()) => command_events.send(command),
+                    Err(err) => console.log(ConsoleMessage::error(err)),
+                }
+            }
+        }
+    }
+
+    for c in typed.into_iter().filter(|c| !c.is_control()) {
+        console.insert(c);
+    }
+}
+
+/// Moves messages into the scrollback, and prints them too, since the console isn't always open.
+pub fn console_output(mut messages: EventReader<ConsoleMessage>, mut console: ResMut<Console>) {
+    for message in messages.iter() {
+        match message.kind {
+            MessageKind::Error => println!("cmd err: {}", message.text),
+            _ => println!("{}", message.text),
+        }
+
+        console.log(message.clone());
+    }
+}
+
+pub fn draw_console(
+    input_state: Res<KeyboardInputState>,
+    console: Res<Console>,
+    mut hud: ResMut<Hud>,
+) {
+    if *input_state != KeyboardInputState::Commands {
+        return;
+    }
+
+    let style = TextStyle::default();
+    let line_height = style.line_height();
+    let console_pos = Vec2::from(CONSOLE_POS);
+
+    let end = console.scrollback.len() - console.scroll;
+    let start = end.saturating_sub(VISIBLE_LINES);
+
+    for (row, message) in console.scrollback.range(start..end).enumerate() {
+        let color = match message.kind {
+            MessageKind::Info => style.color,
+            MessageKind::Error => Vec4::from(ERROR_COLOR),
+            MessageKind::Echo => Vec4::from(ECHO_COLOR),
+        };
+
+        hud.text_styled(
+            console_pos + Vec2::new(0.0, row as f32 * line_height),
+            &message.text,
+            TextStyle { color, ..style },
+        );
+    }
+
+    let input_pos = console_pos + Vec2::new(0.0, VISIBLE_LINES as f32 * line_height);
+    let prompt = "> ";
+
+    hud.text(input_pos, &format!("{}{}", prompt, console.input));
+
+    let cursor_column = (prompt.len() + console.cursor) as f32;
+    hud.text(
+        input_pos + Vec2::new(cursor_column * GLYPH_WIDTH * style.scale, 0.0),
+        "_",
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> Console {
+        let mut console = Console::default();
+
+        for c in text.chars() {
+            console.insert(c);
+        }
+
+        console
+    }
+
+    fn enter(console: &mut Console, line: &str) -> Option<String> {
+        console.set_input(line.to_string());
+        console.take_input()
+    }
+
+    #[test]
+    fn edits_at_the_cursor() {
+        let mut console = typed("ac");
+
+        console.edit(Left);
+        console.insert('b');
+        assert_eq!(console.input, "abc");
+        assert_eq!(console.cursor, 2);
+
+        console.edit(Home);
+        console.edit(Delete);
+        assert_eq!(console.input, "bc");
+
+        console.edit(End);
+        console.edit(Back);
+        assert_eq!(console.input, "b");
+        assert_eq!(console.cursor, 1);
+    }
+
+    #[test]
+    fn cursor_stays_inside_the_input() {
+        let mut console = typed("ab");
+
+        console.edit(Right);
+        assert_eq!(console.cursor, 2);
+
+        console.edit(Home);
+        console.edit(Left);
+        console.edit(Back);
+        assert_eq!(console.cursor, 0);
+        assert_eq!(console.input, "ab");
+    }
+
+    #[test]
+    fn edits_count_chars_not_bytes() {
+        let mut console = typed("héllo");
+
+        console.edit(Home);
+        console.edit(Right);
+        console.edit(Delete);
+        assert_eq!(console.input, "hllo");
+
+        console.insert('ë');
+        assert_eq!(console.input, "hëllo");
+    }
+
+    #[test]
+    fn return_takes_the_trimmed_input() {
+        let mut console = typed("  tp 1 2 3 ");
+
+        assert!(console.edit(Return));
+        assert_eq!(console.take_input(), Some("tp 1 2 3".to_string()));
+        assert_eq!(console.input, "");
+        assert_eq!(console.cursor, 0);
+    }
+
+    #[test]
+    fn blank_lines_are_not_remembered() {
+        let mut console = Console::default();
+
+        assert_eq!(enter(&mut console, "   "), None);
+        assert!(console.history.is_empty());
+    }
+
+    #[test]
+    fn walks_through_history() {
+        let mut console = Console::default();
+        enter(&mut console, "first");
+        enter(&mut console, "second");
+
+        console.edit(Up);
+        assert_eq!(console.input, "second");
+        assert_eq!(console.cursor, 6);
+
+        console.edit(Up);
+        console.edit(Up);
+        assert_eq!(console.input, "first");
+
+        console.edit(Down);
+        assert_eq!(console.input, "second");
+
+        console.edit(Down);
+        assert_eq!(console.input, "");
+        assert_eq!(console.history_index, None);
+    }
+
+    #[test]
+    fn history_skips_repeats_and_forgets_the_oldest() {
+        let mut console = Console::default();
+        enter(&mut console, "same");
+        enter(&mut console, "same");
+        assert_eq!(console.history, ["same"]);
+
+        for i in 0..MAX_HISTORY {
+            enter(&mut console, &i.to_string());
+        }
+
+        assert_eq!(console.history.len(), MAX_HISTORY);
+        assert_eq!(console.history[0], "0");
+    }
+}
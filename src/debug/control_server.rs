@@ -0,0 +1,300 @@
+// A server that lets other programs run console commands, started with --control <port>. It only
+// listens on localhost. Each request is a line of JSON like {"id": 1, "command": "exposure 2"}, and
+// each gets a line back like {"id": 1, "ok": true, "output": ["..."]} or
+// {"id": 1, "ok": false, "error": "..."}. The id can be anything, and is sent back as it was.
+//
+// Commands are run one at a time, in the order they arrive. Each is tagged with its request, and
+// the messages its handler sends with Command::info and error are sent back as its result. Queries
+// like camera_pose and voxel print their answer, so it comes back in output. Commands whose result
+// is only known in a later frame, like screenshot and load, send ReplyLater, and the next request
+// waits until their messages arrive.
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+use bevy::prelude::*;
+use serde_json::{json, Value};
+
+use crate::debug::console::{ConsoleMessage, MessageKind};
+use crate::debug::registry::CommandRegistry;
+use crate::debug::Command;
+
+pub const DEFAULT_PORT: u16 = 7878;
+/// Connections that send a line longer than this without a newline are closed.
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+/// Connections that have more than this waiting to be sent are closed, since they aren't reading
+/// their replies.
+const MAX_OUTPUT_LENGTH: usize = 1024 * 1024;
+
+/// Sent by a command that was given a control_request but whose result is only known in a later
+/// frame. The request stays in flight until a message tagged with it arrives.
+pub struct ReplyLater(pub u64);
+
+struct Connection {
+    id: u64,
+    stream: TcpStream,
+    /// What has been read that doesn't end in a newline yet.
+    buffer: Vec<u8>,
+    /// Replies that haven't been written to the stream yet.
+    output: Vec<u8>,
+    closed: bool,
+}
+
+impl Connection {
+    /// Reads whatever has arrived, and returns the complete lines in it.
+    fn read_lines(&mut self) -> Vec<String> {
+        let mut chunk = [0; 4096];
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(count) => self.buffer.extend_from_slice(&chunk[..count]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim().to_string());
+        }
+
+        if self.buffer.len() > MAX_LINE_LENGTH {
+            self.closed = true;
+        }
+
+        lines
+    }
+
+    fn reply(&mut self, reply: Value) {
+        self.output
+            .extend_from_slice(format!("{}\n", reply).as_bytes());
+
+        if self.output.len() > MAX_OUTPUT_LENGTH {
+            self.closed = true;
+        }
+    }
+
+    /// Writes as much of the output as the stream takes without blocking.
+    fn flush(&mut self) {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(count) => {
+                    self.output.drain(..count);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+struct Request {
+    connection: u64,
+    id: Value,
+    line: String,
+    /// What the command is tagged with, see Command::control_request.
+    number: u64,
+    /// Set once the command has sent ReplyLater.
+    replies_later: bool,
+}
+
+pub struct ControlServer {
+    listener: TcpListener,
+    connections: Vec<Connection>,
+    next_connection_id: u64,
+    next_request: u64,
+    queue: VecDeque<Request>,
+    /// The request whose command was sent, and that hasn't been replied to yet.
+    in_flight: Option<Request>,
+}
+
+impl ControlServer {
+    pub fn bind(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(ControlServer {
+            listener,
+            connections: Vec::new(),
+            next_connection_id: 0,
+            next_request: 0,
+            queue: VecDeque::new(),
+            in_flight: None,
+        })
+    }
+
+    fn accept(&mut self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_err() {
+                continue;
+            }
+
+            self.connections.push(Connection {
+                id: self.next_connection_id,
+                stream,
+                buffer: Vec::new(),
+                output: Vec::new(),
+                closed: false,
+            });
+
+            self.next_connection_id += 1;
+        }
+    }
+
+    fn reply(&mut self, connection: u64, reply: Value) {
+        if let Some(connection) = self.connections.iter_mut().find(|c| c.id == connection) {
+            connection.reply(reply);
+        }
+    }
+}
+
+/// Reads requests, and sends the next valid one as a Command. Runs before the update, so that the
+/// command is handled the same frame.
+pub fn receive_requests(
+    mut server: ResMut<ControlServer>,
+    mut command_events: EventWriter<Command>,
+    registry: Res<CommandRegistry>,
+) {
+    let server = &mut *server;
+
+    server.accept();
+
+    for connection in server.connections.iter_mut() {
+        for line in connection.read_lines() {
+            if line.is_empty() {
+                continue;
+            }
+
+            match parse_request(&line) {
+                Ok((id, line)) => {
+                    server.queue.push_back(Request {
+                        connection: connection.id,
+                        id,
+                        line,
+                        number: server.next_request,
+                        replies_later: false,
+                    });
+
+                    server.next_request += 1;
+                }
+                Err(err) => connection.reply(error_reply(Value::Null, err)),
+            }
+        }
+    }
+
+    server.connections.retain(|connection| !connection.closed);
+
+    if server.in_flight.is_some() {
+        return;
+    }
+
+    while let Some(request) = server.queue.pop_front() {
+        let command = Command::from_line(&request.line)
+            .ok_or_else(|| "the command was empty.".to_string())
+            .and_then(|command| registry.validate(&command).map(|_| command));
+
+        match command {
+            Ok(mut command) => {
+                command.control_request = Some(request.number);
+                command_events.send(command);
+                server.in_flight = Some(request);
+                break;
+            }
+            Err(err) => server.reply(request.connection, error_reply(request.id, err)),
+        }
+    }
+}
+
+/// Replies to the request in flight with the messages tagged with it, unless it is waiting for
+/// a later frame. Then writes out what every connection has waiting.
+pub fn send_replies(
+    mut server: ResMut<ControlServer>,
+    mut messages: EventReader<ConsoleMessage>,
+    mut reply_later: EventReader<ReplyLater>,
+) {
+    let server = &mut *server;
+
+    // read every frame, so that old events aren't seen later.
+    let messages: Vec<&ConsoleMessage> = messages.iter().collect();
+    let reply_later: Vec<u64> = reply_later
+        .iter()
+        .map(|reply_later| reply_later.0)
+        .collect();
+
+    if let Some(request) = server.in_flight.as_mut() {
+        let own: Vec<&ConsoleMessage> = messages
+            .iter()
+            .copied()
+            .filter(|message| message.control_request == Some(request.number))
+            .collect();
+
+        request.replies_later |= reply_later.contains(&request.number);
+
+        if !request.replies_later || !own.is_empty() {
+            let request = server.in_flight.take().unwrap();
+            server.reply(request.connection, command_reply(request.id, &own));
+        }
+    }
+
+    for connection in server.connections.iter_mut() {
+        connection.flush();
+    }
+
+    server.connections.retain(|connection| !connection.closed);
+}
+
+fn command_reply(id: Value, messages: &[&ConsoleMessage]) -> Value {
+    let errors: Vec<&str> = messages
+        .iter()
+        .filter(|message| message.kind == MessageKind::Error)
+        .map(|message| message.text.as_str())
+        .collect();
+
+    if !errors.is_empty() {
+        return error_reply(id, errors.join("\n"));
+    }
+
+    let output: Vec<&str> = messages
+        .iter()
+        .map(|message| message.text.as_str())
+        .collect();
+
+    json!({ "id": id, "ok": true, "output": output })
+}
+
+fn parse_request(line: &str) -> Result<(Value, String), String> {
+    let request: Value =
+        serde_json::from_str(line).map_err(|err| format!("could not read request, {}.", err))?;
+
+    let command = request
+        .get("command")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "expected a command string in the request.".to_string())?;
+
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+
+    Ok((id, command.to_string()))
+}
+
+fn error_reply(id: Value, error: String) -> Value {
+    json!({ "id": id, "ok": false, "error": error })
+}
@@ -1,20 +1,52 @@
+use crate::debug::console::ConsoleMessage;
+use crate::debug::control_server::ReplyLater;
 use crate::debug::Command;
 use crate::input::MousePos;
 use crate::world::model_loader::*;
 use crate::world::{chunk_position_to_index, ClearWorld, CHUNK_COUNT, CHUNK_SIZE};
 use crate::{Background, GPUData, ModelHolder};
+use bevy::asset::LoadState;
 use bevy::input::mouse::MouseButtonInput;
 use bevy::prelude::*;
 
+/// A model asked for with `load` that hasn't finished loading yet.
+pub struct PendingLoad {
+    name: String,
+    handle: Handle<Model>,
+    control_request: Option<u64>,
+}
+
+/// `load <model>` replaces the world with a model. Whether it loaded is only known a few frames
+/// later, so that is when the result is sent.
 pub fn load_vox(
     mut debug_commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut reply_later: EventWriter<ReplyLater>,
+    mut pending: Local<Vec<PendingLoad>>,
     asset_server: Res<AssetServer>,
     mut commands: Commands,
     mut clear_world_events: EventWriter<ClearWorld>,
 ) {
     for cmd in debug_commands.iter().filter(|cmd| cmd.is("load")) {
         let model_name = cmd.get_arg(0);
+
+        if model_name.is_empty() {
+            console.send(cmd.error("load: expected the name of a model."));
+            continue;
+        }
+
         let model: Handle<Model> = asset_server.load(&*format!("models/{model_name}.vox"));
+
+        pending.push(PendingLoad {
+            name: model_name.to_string(),
+            handle: model.clone(),
+            control_request: cmd.control_request,
+        });
+
+        if let Some(request) = cmd.control_request {
+            reply_later.send(ReplyLater(request));
+        }
+
         let model = crate::world::model_type::ModelHolder::new_static(model);
 
         commands
@@ -24,4 +56,15 @@ pub fn load_vox(
 
         clear_world_events.send(ClearWorld::default());
     }
+
+    pending.retain(|load| {
+        let message = match asset_server.get_load_state(&load.handle) {
+            LoadState::Loaded => ConsoleMessage::info(format!("loaded {}.", load.name)),
+            LoadState::Failed => ConsoleMessage::error(format!("could not load {}.", load.name)),
+            _ => return true,
+        };
+
+        console.send(message.for_request(load.control_request));
+        false
+    });
 }
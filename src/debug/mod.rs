@@ -1,221 +1,285 @@
 #![allow(dead_code)]
 
-use crate::input::KeyboardInputState;
-use crate::GPUData;
-use bevy::core::FixedTimestep;
-use bevy::prelude::KeyCode::*;
+use bevy::app::Events;
 use bevy::prelude::*;
-use lazy_static::lazy_static;
-use std::collections::HashMap;
+use registry::{AddCommands, ArgKind, ArgSpec, CommandSpec, ON_OFF};
+use std::str::FromStr;
 
+use crate::options::StartupOptions;
+use crate::settings::Settings;
+
+pub mod console;
+pub mod control_server;
 mod load_vox;
 mod log_framerate;
+pub mod registry;
+pub mod script;
+pub mod stats_overlay;
 mod world_edit;
 
-const DEBUG_TIME_STEP: f64 = 1.0 / 5.0;
-
 #[derive(Default)]
 pub struct CtklrDebugPlugin;
 
 impl Plugin for CtklrDebugPlugin {
     fn build(&self, app: &mut App) {
+        let settings = app
+            .world
+            .get_resource::<Settings>()
+            .cloned()
+            .unwrap_or_default();
+
         app.add_system_set(
             SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(DEBUG_TIME_STEP))
+                .with_run_criteria(settings.debug.framerate_log_timestep())
                 .with_system(log_framerate::log_framerate),
         )
-        .add_system(command_input)
-        .add_system(world_edit::edit_world)
+        .add_system(console::console_input.label("console_input"))
+        .add_system(console::console_output.label("console_output"))
+        .add_system(
+            console::draw_console
+                .after("console_input")
+                .after("console_output"),
+        )
+        .add_system(world_edit::selection_commands)
+        .add_system(world_edit::voxel_query)
+        .add_system(world_edit::update_selection.label("select"))
+        .add_system(world_edit::edit_world.after("select"))
         .add_system(load_vox::load_vox)
-        .add_event::<Command>();
-    }
-}
+        .add_system(registry::help_command)
+        .add_system(script::script_commands)
+        .add_system(script::run_scripts)
+        .add_plugin(stats_overlay::StatsOverlayPlugin)
+        .add_event::<Command>()
+        .add_event::<console::ConsoleMessage>()
+        .add_event::<control_server::ReplyLater>()
+        .insert_resource(console::Console::default())
+        .insert_resource(world_edit::Selection::default())
+        .add_commands(registry::COMMANDS)
+        .add_commands(script::COMMANDS)
+        .add_commands(COMMANDS);
 
-lazy_static! {
-    static ref KEYBOARD_MAP: HashMap<KeyCode, u32> = HashMap::from([
-        (A, 1),
-        (B, 2),
-        (C, 3),
-        (D, 4),
-        (E, 5),
-        (F, 6),
-        (G, 7),
-        (H, 8),
-        (I, 9),
-        (J, 10),
-        (K, 11),
-        (L, 12),
-        (M, 13),
-        (N, 14),
-        (O, 15),
-        (P, 16),
-        (Q, 17),
-        (R, 18),
-        (S, 19),
-        (T, 20),
-        (U, 21),
-        (V, 22),
-        (W, 23),
-        (X, 24),
-        (Y, 25),
-        (Z, 26),
-        (Key1, 27),
-        (Key2, 28),
-        (Key3, 29),
-        (Key4, 30),
-        (Key5, 31),
-        (Key6, 32),
-        (Key7, 33),
-        (Key8, 34),
-        (Key9, 35),
-        (Key0, 36),
-        (Period, 37),
-        (Minus, 38),
-        (Space, 39),
-    ]);
-    static ref NUM_TO_CHAR: HashMap<u32, char> = HashMap::from([
-        (1, 'a'),
-        (2, 'b'),
-        (3, 'c'),
-        (4, 'd'),
-        (5, 'e'),
-        (6, 'f'),
-        (7, 'g'),
-        (8, 'h'),
-        (9, 'i'),
-        (10, 'j'),
-        (11, 'k'),
-        (12, 'l'),
-        (13, 'm'),
-        (14, 'n'),
-        (15, 'o'),
-        (16, 'p'),
-        (17, 'q'),
-        (18, 'r'),
-        (19, 's'),
-        (20, 't'),
-        (21, 'u'),
-        (22, 'v'),
-        (23, 'w'),
-        (24, 'x'),
-        (25, 'y'),
-        (26, 'z'),
-        (27, '1'),
-        (28, '2'),
-        (29, '3'),
-        (30, '4'),
-        (31, '5'),
-        (32, '6'),
-        (33, '7'),
-        (34, '8'),
-        (35, '9'),
-        (36, '0'),
-        (37, '.'),
-        (38, '-'),
-        (39, ' '),
-    ]);
-}
+        let options = app
+            .world
+            .get_resource::<StartupOptions>()
+            .cloned()
+            .unwrap_or_default();
 
-pub fn command_input(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut input_state: ResMut<KeyboardInputState>,
-    mut gpu_data: ResMut<GPUData>,
-    mut command_events: EventWriter<Command>,
-) {
-    if keyboard_input.pressed(LShift) && keyboard_input.just_pressed(C) {
-        *input_state = KeyboardInputState::Commands;
-        return;
-    }
-
-    if *input_state != KeyboardInputState::Commands {
-        return;
-    }
-
-    if keyboard_input.pressed(Escape) {
-        *input_state = KeyboardInputState::default();
-        clear_text(&mut gpu_data.text_to_show);
-        return;
-    } else if keyboard_input.pressed(Return) {
-        *input_state = KeyboardInputState::default();
-        command_events.send(parse_command(&gpu_data.text_to_show));
-        clear_text(&mut gpu_data.text_to_show);
-        return;
-    }
-
-    for input in keyboard_input.get_just_pressed() {
-        let new_char_pos = gpu_data
-            .text_to_show
-            .iter()
-            .position(|&c| c == 0)
-            .expect("no more room in command!");
-
-        if input == &Back {
-            gpu_data.text_to_show[new_char_pos - 1] = 0;
-        } else {
-            gpu_data.text_to_show[new_char_pos] = *KEYBOARD_MAP.get(input).unwrap_or(&0);
-        }
-    }
-}
+        let mut scripts = script::Scripts::default();
+        let mut errors = script::queue_startup_scripts(&mut scripts, &options.exec);
 
-fn clear_text(text: &mut [u32; 256]) {
-    for c in text.iter_mut() {
-        *c = 0
-    }
-}
+        if let Some(port) = options.control {
+            match control_server::ControlServer::bind(port) {
+                Ok(server) => {
+                    app.insert_resource(server)
+                        .add_system_to_stage(CoreStage::PreUpdate, control_server::receive_requests)
+                        .add_system_to_stage(CoreStage::PostUpdate, control_server::send_replies);
+                }
+                Err(err) => errors.push(format!("could not start the control server, {}.", err)),
+            }
+        }
 
-fn parse_command(text: &[u32; 256]) -> Command {
-    let mut cmd_string = String::new();
+        let mut console_messages = app
+            .world
+            .get_resource_mut::<Events<console::ConsoleMessage>>()
+            .unwrap();
 
-    for c in text {
-        if let Some(char_to_push) = NUM_TO_CHAR.get(c) {
-            cmd_string.push(*char_to_push);
+        for err in errors {
+            console_messages.send(console::ConsoleMessage::error(err));
         }
-    }
-
-    let mut cmd_split = cmd_string.split(" ");
-    let function = cmd_split.next().expect("empty command").to_string();
-    let arguments = cmd_split.map(|str| str.to_string()).collect();
 
-    Command {
-        function,
-        arguments,
+        app.insert_resource(scripts);
     }
 }
 
+const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "load",
+        description: "clears the world and loads a model from assets/models.",
+        args: &[ArgSpec::new(
+            "model",
+            ArgKind::Asset {
+                dir: "assets/models",
+                extension: "vox",
+            },
+        )],
+    },
+    CommandSpec {
+        name: "select",
+        description: "picks whether editing selects whole tiles or single voxels.",
+        args: &[ArgSpec::new("mode", ArgKind::Enum(&["tile", "voxel"]))],
+    },
+    CommandSpec {
+        name: "voxel",
+        description: "prints the voxel at a position, 0 being air.",
+        args: &[
+            ArgSpec::new("x", ArgKind::Int),
+            ArgSpec::new("y", ArgKind::Int),
+            ArgSpec::new("z", ArgKind::Int),
+        ],
+    },
+    CommandSpec {
+        name: "hud",
+        description: "shows or hides the frame timing overlay, also toggled with F3.",
+        args: &[ArgSpec::new("visible", ON_OFF)],
+    },
+];
+
 #[derive(Debug)]
 pub struct Command {
     pub function: String,
     pub arguments: Vec<String>,
+    /// Set when the command came from the control server, so that messages about it can be sent
+    /// back to whoever asked for it.
+    pub control_request: Option<u64>,
 }
 
 impl Command {
+    /// Splits a line into the function and its arguments, at whitespace that isn't inside double
+    /// quotes, so `screenshot "front door"` has one argument. Returns None if the line is blank.
+    pub fn from_line(line: &str) -> Option<Command> {
+        let mut words = split_words(line).into_iter();
+        let function = words.next()?;
+        let arguments = words.collect();
+
+        Some(Command {
+            function,
+            arguments,
+            control_request: None,
+        })
+    }
+
+    /// Use these instead of ConsoleMessage::info and error for the result of a command, so that it
+    /// also goes back to the control server client that sent it.
+    pub fn info(&self, text: impl Into<String>) -> console::ConsoleMessage {
+        console::ConsoleMessage::info(text).for_request(self.control_request)
+    }
+
+    pub fn error(&self, text: impl Into<String>) -> console::ConsoleMessage {
+        console::ConsoleMessage::error(text).for_request(self.control_request)
+    }
+
     pub fn is(&self, check: &str) -> bool {
-        check.to_string() == self.function
+        check == self.function
     }
 
-    //TODO: use generics here
+    /// Parses the argument at index, or says what was wrong with it.
+    pub fn parse_arg<T: FromStr>(&self, index: usize) -> Result<T, String> {
+        let arg = self
+            .arguments
+            .get(index)
+            .ok_or_else(|| format!("{}: missing argument {}.", self.function, index + 1))?;
 
-    pub fn parse(&self) -> u32 {
-        self.parse_arg_at(0)
+        arg.parse::<T>().map_err(|_| {
+            format!(
+                "{}: expected a(n) {} for argument {}, got {}.",
+                self.function,
+                std::any::type_name::<T>(),
+                index + 1,
+                arg
+            )
+        })
     }
 
-    pub fn parse_arg_at(&self, index: usize) -> u32 {
-        self.arguments[index]
-            .parse::<u32>()
-            .unwrap_or_else(|_| panic!("cmd err: expected a(n) u32 for arg {}.", index))
+    /// Parses three arguments in a row, starting at index, as the parts of a vector.
+    pub fn parse_vec3(&self, index: usize) -> Result<Vec3, String> {
+        Ok(Vec3::new(
+            self.parse_arg(index)?,
+            self.parse_arg(index + 1)?,
+            self.parse_arg(index + 2)?,
+        ))
     }
 
-    pub fn parse_f32(&self) -> f32 {
-        self.parse_arg_at_f32(0)
+    /// The argument at index, or an empty string if there isn't one.
+    pub fn get_arg(&self, index: usize) -> &str {
+        self.arguments.get(index).map_or("", String::as_str)
     }
+}
+
+/// The quotes themselves are left out of the words. A quote that is never closed runs to the end of
+/// the line.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    words.extend(word);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        Command::from_line(line).unwrap().arguments
+    }
+
+    #[test]
+    fn splits_on_any_whitespace() {
+        let cmd = Command::from_line("  tp 1\t 2   3 ").unwrap();
 
-    pub fn parse_arg_at_f32(&self, index: usize) -> f32 {
-        self.arguments[index]
-            .parse::<f32>()
-            .unwrap_or_else(|_| panic!("cmd err: expected a(n) u32 for arg {}.", index))
+        assert_eq!(cmd.function, "tp");
+        assert_eq!(cmd.arguments, ["1", "2", "3"]);
     }
 
-    pub fn get_arg(&self, index: usize) -> &String {
-        &self.arguments[index]
+    #[test]
+    fn blank_lines_are_not_commands() {
+        assert!(Command::from_line("").is_none());
+        assert!(Command::from_line(" \t ").is_none());
+    }
+
+    #[test]
+    fn quotes_keep_words_together() {
+        assert_eq!(args("screenshot \"front door\""), ["front door"]);
+        assert_eq!(
+            args("set world.anchored_colors \"[1, 2]\""),
+            ["world.anchored_colors", "[1, 2]"]
+        );
+        assert_eq!(args("say a\"b c\"d e"), ["ab cd", "e"]);
+    }
+
+    #[test]
+    fn empty_quotes_are_an_argument() {
+        assert_eq!(args("say \"\" x"), ["", "x"]);
+    }
+
+    #[test]
+    fn unclosed_quotes_run_to_the_end() {
+        assert_eq!(args("say \"a  b"), ["a  b"]);
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let cmd = Command::from_line("tp 1 -2.5 3").unwrap();
+
+        assert_eq!(cmd.parse_arg::<u32>(0), Ok(1));
+        assert_eq!(cmd.parse_vec3(0), Ok(Vec3::new(1.0, -2.5, 3.0)));
+        assert_eq!(cmd.get_arg(3), "");
+    }
+
+    #[test]
+    fn parse_errors_say_what_was_wrong() {
+        let cmd = Command::from_line("resolution 100 wide").unwrap();
+
+        assert_eq!(
+            cmd.parse_arg::<u32>(2),
+            Err("resolution: missing argument 3.".to_string())
+        );
+        assert_eq!(
+            cmd.parse_arg::<u32>(1),
+            Err("resolution: expected a(n) u32 for argument 2, got wide.".to_string())
+        );
+        assert!(cmd.parse_vec3(0).is_err());
     }
 }
@@ -0,0 +1,338 @@
+// Every command has to be registered, with what it does and what arguments it takes, so that the
+// console can check a command before it is sent, print help for it, and tab complete it. Each
+// module keeps a COMMANDS list next to the system that handles them, and its plugin registers them
+// with App::add_commands.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::Command;
+
+/// For commands that turn something on or off.
+pub const ON_OFF: ArgKind = ArgKind::Enum(&["on", "off"]);
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArgKind {
+    /// A whole number that fits in an i32.
+    Int,
+    /// A whole number from 0 up that fits in a u32, for counts and sizes.
+    UInt,
+    Float,
+    /// Three floats, given as three separate words.
+    Vec3,
+    /// Any single word.
+    Text,
+    /// One of a fixed set of words.
+    Enum(&'static [&'static str]),
+    /// The name of a file in dir with the given extension, without the extension.
+    Asset {
+        dir: &'static str,
+        extension: &'static str,
+    },
+    /// The name of a registered command.
+    CommandName,
+}
+
+impl ArgKind {
+    /// How many words of the command line an argument of this kind takes up.
+    fn word_count(self) -> usize {
+        match self {
+            ArgKind::Vec3 => 3,
+            _ => 1,
+        }
+    }
+
+    fn check(self, name: &str, words: &[&str], registry: &CommandRegistry) -> Result<(), String> {
+        let all_parse = |parse: fn(&str) -> bool| words.iter().all(|word| parse(word));
+
+        let ok = match self {
+            ArgKind::Int => all_parse(|word| word.parse::<i32>().is_ok()),
+            ArgKind::UInt => all_parse(|word| word.parse::<u32>().is_ok()),
+            ArgKind::Float | ArgKind::Vec3 => all_parse(|word| word.parse::<f32>().is_ok()),
+            ArgKind::Text => true,
+            ArgKind::Enum(values) => values.contains(&words[0]),
+            ArgKind::Asset { dir, extension } => Path::new(dir)
+                .join(format!("{}.{}", words[0], extension))
+                .is_file(),
+            ArgKind::CommandName => registry.get(words[0]).is_some(),
+        };
+
+        if ok {
+            return Ok(());
+        }
+
+        let expected = match self {
+            ArgKind::Int => "a whole number".to_string(),
+            ArgKind::UInt => "a whole number from 0 up".to_string(),
+            ArgKind::Float => "a number".to_string(),
+            ArgKind::Vec3 => "three numbers".to_string(),
+            ArgKind::Text => unreachable!(),
+            ArgKind::Enum(values) => values.join(", "),
+            ArgKind::Asset { dir, extension } => format!("the name of a .{} in {}", extension, dir),
+            ArgKind::CommandName => "the name of a command".to_string(),
+        };
+
+        Err(format!(
+            "expected {} for {}, got {}.",
+            expected,
+            name,
+            words.join(" ")
+        ))
+    }
+
+    /// Words that an argument of this kind could be.
+    fn candidates(self, registry: &CommandRegistry) -> Vec<String> {
+        match self {
+            ArgKind::Enum(values) => values.iter().map(|value| value.to_string()).collect(),
+            ArgKind::Asset { dir, extension } => asset_names(dir, extension),
+            ArgKind::CommandName => registry
+                .commands
+                .keys()
+                .map(|name| name.to_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn asset_names(dir: &str, extension: &str) -> Vec<String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == extension))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+
+    names.sort();
+    names
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    /// Optional arguments can only be followed by other optional arguments.
+    pub optional: bool,
+}
+
+impl ArgSpec {
+    pub const fn new(name: &'static str, kind: ArgKind) -> Self {
+        ArgSpec {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub const fn optional(self) -> Self {
+        ArgSpec {
+            optional: true,
+            ..self
+        }
+    }
+
+    fn usage(&self) -> String {
+        let inner = match self.kind {
+            ArgKind::Enum(values) => values.join("|"),
+            _ => self.name.to_string(),
+        };
+
+        if self.optional {
+            format!("[{}]", inner)
+        } else {
+            format!("<{}>", inner)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub args: &'static [ArgSpec],
+}
+
+impl CommandSpec {
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+
+        for arg in self.args {
+            usage.push(' ');
+            usage.push_str(&arg.usage());
+        }
+
+        usage
+    }
+
+    /// Which argument the word at index is part of.
+    fn arg_at_word(&self, index: usize) -> Option<&ArgSpec> {
+        let mut start = 0;
+
+        for arg in self.args {
+            start += arg.kind.word_count();
+
+            if index < start {
+                return Some(arg);
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, CommandSpec>,
+}
+
+impl CommandRegistry {
+    pub fn register(&mut self, spec: CommandSpec) {
+        if self.commands.insert(spec.name, spec).is_some() {
+            warn!("the command {} was registered twice", spec.name);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.commands.get(name)
+    }
+
+    /// Checks that a command exists and that its arguments match what it was registered with.
+    pub fn validate(&self, cmd: &Command) -> Result<(), String> {
+        let spec = self
+            .get(&cmd.function)
+            .ok_or_else(|| format!("unknown command {}, try help.", cmd.function))?;
+
+        let usage_error = |err: String| format!("{}\nusage: {}", err, spec.usage());
+
+        let mut words = cmd.arguments.iter().map(String::as_str);
+
+        for arg in spec.args {
+            let arg_words: Vec<&str> = words.by_ref().take(arg.kind.word_count()).collect();
+
+            if arg_words.is_empty() && arg.optional {
+                break;
+            }
+
+            if arg_words.len() < arg.kind.word_count() {
+                return Err(usage_error(format!("{}: missing {}.", spec.name, arg.name)));
+            }
+
+            arg.kind
+                .check(arg.name, &arg_words, self)
+                .map_err(|err| usage_error(format!("{}: {}", spec.name, err)))?;
+        }
+
+        if words.next().is_some() {
+            return Err(usage_error(format!("{}: too many arguments.", spec.name)));
+        }
+
+        Ok(())
+    }
+
+    /// What the word being typed could be, given the words before it.
+    pub fn complete(&self, words_before: &[&str], partial: &str) -> Vec<String> {
+        let candidates = match words_before.split_first() {
+            None => self.commands.keys().map(|name| name.to_string()).collect(),
+            Some((name, args)) => self
+                .get(name)
+                .and_then(|spec| spec.arg_at_word(args.len()))
+                .map_or_else(Vec::new, |arg| arg.kind.candidates(self)),
+        };
+
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(partial))
+            .collect()
+    }
+}
+
+pub trait AddCommands {
+    fn add_commands(&mut self, specs: &[CommandSpec]) -> &mut Self;
+}
+
+impl AddCommands for App {
+    fn add_commands(&mut self, specs: &[CommandSpec]) -> &mut Self {
+        let mut registry = self
+            .world
+            .get_resource_or_insert_with(CommandRegistry::default);
+
+        for spec in specs {
+            registry.register(*spec);
+        }
+
+        self
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[CommandSpec {
+    name: "help",
+    description: "lists every command, or explains one.",
+    args: &[ArgSpec::new("command", ArgKind::CommandName).optional()],
+}];
+
+/// `help` lists every command and what it does, `help <command>` shows how to use one.
+pub fn help_command(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    registry: Res<CommandRegistry>,
+) {
+    for cmd in commands.iter().filter(|cmd| cmd.is("help")) {
+        let text = match registry.get(cmd.get_arg(0)) {
+            Some(spec) => format!("{}\n  {}", spec.usage(), spec.description),
+            None => registry
+                .commands
+                .values()
+                .map(|spec| format!("{} - {}", spec.name, spec.description))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+
+        console.send(cmd.info(text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPECS: &[CommandSpec] = &[
+        CommandSpec {
+            name: "voxel",
+            description: "",
+            args: &[ArgSpec::new("x", ArgKind::Int)],
+        },
+        CommandSpec {
+            name: "wait",
+            description: "",
+            args: &[ArgSpec::new("frames", ArgKind::UInt)],
+        },
+    ];
+
+    fn validate(line: &str) -> Result<(), String> {
+        let mut registry = CommandRegistry::default();
+
+        for spec in SPECS {
+            registry.register(*spec);
+        }
+
+        registry.validate(&Command::from_line(line).unwrap())
+    }
+
+    #[test]
+    fn whole_numbers_fit_in_what_the_handler_parses() {
+        assert!(validate("voxel -1").is_ok());
+        assert!(validate("voxel 2147483648").is_err());
+        assert!(validate("wait 4294967295").is_ok());
+        assert!(validate("wait -1").is_err());
+        assert!(validate("wait 5000000000").is_err());
+        assert!(validate("wait 1.5").is_err());
+    }
+}
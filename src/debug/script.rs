@@ -0,0 +1,172 @@
+// Scripts are text files of console commands, one per line, run with `exec <file>`. Blank lines and
+// anything after a # are ignored, and `wait <frames>` pauses the script for that many frames, so
+// that something like a model can finish loading before the next command. autoexec.cfg is run when
+// the app starts, if there is one, followed by any files given with --exec.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandRegistry, CommandSpec};
+use crate::debug::Command;
+
+pub const AUTOEXEC: &str = "autoexec.cfg";
+/// Scripts that exec themselves, or each other in a loop, stop here instead of going forever.
+const MAX_DEPTH: usize = 16;
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "exec",
+        description: "runs every command in a file, .cfg can be left off.",
+        args: &[ArgSpec::new("file", ArgKind::Text)],
+    },
+    CommandSpec {
+        name: "wait",
+        description: "in a script, waits that many frames before the next command.",
+        args: &[ArgSpec::new("frames", ArgKind::UInt)],
+    },
+];
+
+struct Script {
+    file: PathBuf,
+    /// The lines left to run, with their line numbers for errors.
+    lines: VecDeque<(usize, String)>,
+}
+
+/// The scripts being run. The last one is the innermost, it has to finish before the one that ran
+/// it carries on.
+#[derive(Default)]
+pub struct Scripts {
+    running: Vec<Script>,
+    frames_to_wait: u32,
+}
+
+impl Scripts {
+    pub fn exec(&mut self, file: &str) -> Result<(), String> {
+        if self.running.len() == MAX_DEPTH {
+            return Err(format!("exec: scripts can only run {} deep.", MAX_DEPTH));
+        }
+
+        let mut file = PathBuf::from(file);
+
+        if !file.is_file() && file.extension().is_none() {
+            file.set_extension("cfg");
+        }
+
+        let text = std::fs::read_to_string(&file)
+            .map_err(|err| format!("exec: could not read {}, {}.", file.display(), err))?;
+
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, strip_comment(line).trim().to_string()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+
+        self.running.push(Script { file, lines });
+
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("")
+}
+
+/// Queues autoexec.cfg and the files given with --exec, in that order.
+pub fn queue_startup_scripts(scripts: &mut Scripts, exec: &[String]) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    // the last script pushed runs first.
+    for file in exec.iter().rev() {
+        if let Err(err) = scripts.exec(file) {
+            errors.push(err);
+        }
+    }
+
+    if Path::new(AUTOEXEC).is_file() {
+        if let Err(err) = scripts.exec(AUTOEXEC) {
+            errors.push(err);
+        }
+    }
+
+    errors
+}
+
+/// Handles exec and wait typed into the console. Commands in scripts are run by run_scripts.
+pub fn script_commands(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut scripts: ResMut<Scripts>,
+) {
+    for cmd in commands.iter() {
+        let result = match cmd.function.as_str() {
+            "exec" => scripts.exec(cmd.get_arg(0)),
+            "wait" => Err("wait: only does something in a script.".to_string()),
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            console.send(cmd.error(err));
+        }
+    }
+}
+
+/// Sends the commands in the running scripts, up to the next wait.
+pub fn run_scripts(
+    mut scripts: ResMut<Scripts>,
+    mut command_events: EventWriter<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    registry: Res<CommandRegistry>,
+) {
+    if scripts.frames_to_wait > 0 {
+        scripts.frames_to_wait -= 1;
+        return;
+    }
+
+    while let Some(script) = scripts.running.last_mut() {
+        let (line_number, line) = match script.lines.pop_front() {
+            Some(line) => line,
+            None => {
+                scripts.running.pop();
+                continue;
+            }
+        };
+
+        let file = script.file.display().to_string();
+        let command = match Command::from_line(&line) {
+            Some(command) => command,
+            None => continue,
+        };
+
+        let result = registry
+            .validate(&command)
+            .and_then(|_| match command.function.as_str() {
+                "exec" => scripts.exec(command.get_arg(0)),
+                "wait" => command
+                    .parse_arg(0)
+                    .map(|frames| scripts.frames_to_wait = frames),
+                _ => {
+                    command_events.send(command);
+                    Ok(())
+                }
+            });
+
+        if let Err(err) = result {
+            console.send(ConsoleMessage::error(format!(
+                "{}:{}: {}",
+                file, line_number, err
+            )));
+        }
+
+        if scripts.frames_to_wait > 0 {
+            return;
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// An overlay with frame timings and some numbers about the world, drawn with the Hud text API. The
+// numbers come from bevy's diagnostics, so they can also be read by anything else that wants them.
+
+use std::collections::VecDeque;
+
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticId, Diagnostics, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin,
+};
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::Command;
+use crate::input::actions::{Action, ActionState};
+use crate::input::KeyboardInputState;
+use crate::rendering::camera::Camera;
+use crate::rendering::text::{Hud, TextStyle};
+use crate::world::voxel_world::VoxelWorld;
+use crate::world::{WorldUpdates, CHUNK_VOL};
+use crate::GPUData;
+
+pub const FILLED_CHUNKS: DiagnosticId =
+    DiagnosticId::from_u128(0x6b5f_1c2e_8d3a_4f70_9e21_0c4b_7a11_d3e5);
+pub const PENDING_UPLOAD_BYTES: DiagnosticId =
+    DiagnosticId::from_u128(0x2f8e_94a0_51c7_4b3d_a6e2_77d9_0b14_c862);
+
+const DIAGNOSTIC_HISTORY: usize = 20;
+
+/// How many frames the graph shows, one bar each. Matches the size of GPUData::frame_time_graph.
+const GRAPH_FRAMES: usize = 64;
+/// A frame that takes this long fills the graph from bottom to top.
+const GRAPH_MAX_MS: f64 = 50.0;
+
+/// Whether the overlay is shown, and the frame times the graph is drawn from.
+pub struct StatsOverlay {
+    pub visible: bool,
+    frame_times: VecDeque<f64>,
+}
+
+impl Default for StatsOverlay {
+    fn default() -> Self {
+        StatsOverlay {
+            visible: false,
+            frame_times: VecDeque::with_capacity(GRAPH_FRAMES),
+        }
+    }
+}
+
+pub struct StatsOverlayPlugin;
+
+impl Plugin for StatsOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .add_plugin(EntityCountDiagnosticsPlugin::default())
+            .add_startup_system(setup_world_diagnostics)
+            .add_system(
+                world_diagnostics
+                    .after(crate::world::CHANGE_WORLD)
+                    .before(crate::world::UPDATE_WORLD),
+            )
+            .add_system(update_stats_overlay)
+            .insert_resource(StatsOverlay::default());
+    }
+}
+
+fn setup_world_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(
+        FILLED_CHUNKS,
+        "filled_chunks",
+        DIAGNOSTIC_HISTORY,
+    ));
+    diagnostics.add(Diagnostic::new(
+        PENDING_UPLOAD_BYTES,
+        "pending_upload_bytes",
+        DIAGNOSTIC_HISTORY,
+    ));
+}
+
+/// Runs after the world has been drawn to, and before the changes are uploaded, so that the
+/// upload size is what this frame sends to the gpu.
+fn world_diagnostics(
+    mut diagnostics: ResMut<Diagnostics>,
+    voxel_world: Res<VoxelWorld>,
+    world_updates: Res<WorldUpdates>,
+) {
+    let filled_chunks = voxel_world
+        .filled_chunks
+        .iter()
+        .filter(|filled| **filled)
+        .count();
+
+    let chunk_bytes = CHUNK_VOL * std::mem::size_of::<u32>();
+    let upload_bytes: usize = world_updates
+        .values()
+        .map(|updates| updates.len() * chunk_bytes)
+        .sum();
+
+    diagnostics.add_measurement(FILLED_CHUNKS, filled_chunks as f64);
+    diagnostics.add_measurement(PENDING_UPLOAD_BYTES, upload_bytes as f64);
+}
+
+/// F3 (toggle_stats) shows and hides the overlay, as do `hud on` and `hud off`.
+#[allow(clippy::too_many_arguments)]
+fn update_stats_overlay(
+    actions: Res<ActionState>,
+    input_state: Res<KeyboardInputState>,
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut overlay: ResMut<StatsOverlay>,
+    mut hud: ResMut<Hud>,
+    mut gpu_data: ResMut<GPUData>,
+    diagnostics: Res<Diagnostics>,
+    camera: Res<Camera>,
+) {
+    let was_visible = overlay.visible;
+
+    if actions.just_pressed(Action::ToggleStats) {
+        overlay.visible = !overlay.visible;
+    }
+
+    for cmd in commands.iter().filter(|cmd| cmd.is("hud")) {
+        match cmd.get_arg(0) {
+            "on" => overlay.visible = true,
+            "off" => overlay.visible = false,
+            other => console.send(cmd.error(format!("expected on or off, got {}.", other))),
+        }
+    }
+
+    let latest = |id| diagnostics.get(id).and_then(Diagnostic::value);
+
+    if let Some(frame_time) = latest(FrameTimeDiagnosticsPlugin::FRAME_TIME) {
+        if overlay.frame_times.len() == GRAPH_FRAMES {
+            overlay.frame_times.pop_front();
+        }
+
+        overlay.frame_times.push_back(frame_time * 1000.0);
+    }
+
+    if !overlay.visible {
+        if was_visible {
+            gpu_data.show_hud = 0;
+        }
+
+        return;
+    }
+
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(Diagnostic::average)
+            .unwrap_or(0.0)
+    };
+
+    let forward = camera.forward();
+    let yaw = forward.x.atan2(forward.z).to_degrees();
+    let pitch = forward.y.clamp(-1.0, 1.0).asin().to_degrees();
+
+    let text = format!(
+        "fps: {:.0}  frame: {:.2}ms\n\
+         pos: {:.1}, {:.1}, {:.1}\n\
+         yaw: {:.0}  pitch: {:.0}  fov: {:.0}\n\
+         entities: {}  chunks: {}  upload: {}kb",
+        average(FrameTimeDiagnosticsPlugin::FPS),
+        overlay.frame_times.back().copied().unwrap_or(0.0),
+        camera.position.x,
+        camera.position.y,
+        camera.position.z,
+        yaw,
+        pitch,
+        camera.fov.to_degrees(),
+        latest(EntityCountDiagnosticsPlugin::ENTITY_COUNT).unwrap_or(0.0),
+        latest(FILLED_CHUNKS).unwrap_or(0.0),
+        (latest(PENDING_UPLOAD_BYTES).unwrap_or(0.0) / 1024.0).ceil()
+    );
+
+    // the console is drawn in the same place, and covers the text while it is open.
+    if *input_state != KeyboardInputState::Commands {
+        hud.text_styled(
+            Vec2::new(8.0, 8.0),
+            &text,
+            TextStyle {
+                color: Vec4::new(1.0, 1.0, 0.6, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+
+    // each bar is a byte, four to a u32, the same way post_processing.glsl unpacks them.
+    let mut graph = [0; GRAPH_FRAMES / 4];
+    let offset = GRAPH_FRAMES - overlay.frame_times.len();
+
+    for (index, ms) in overlay.frame_times.iter().enumerate() {
+        let bar = index + offset;
+        let height = ((ms / GRAPH_MAX_MS).clamp(0.0, 1.0) * 255.0).round() as u32;
+
+        graph[bar / 4] |= height << (bar % 4 * 8);
+    }
+
+    gpu_data.frame_time_graph = graph;
+    gpu_data.show_hud = 1;
+}
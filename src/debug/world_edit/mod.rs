@@ -1,10 +1,22 @@
 use bevy::input::mouse::MouseButtonInput;
 use bevy::prelude::*;
 use crate::{Background, GPUData, ModelHolder};
+use crate::debug::console::ConsoleMessage;
+use crate::debug::Command;
+use crate::input::actions::{Action, ActionState};
 use crate::input::MousePos;
-use crate::world::{CHUNK_SIZE, CHUNK_COUNT, chunk_position_to_index, ClearWorld};
+use crate::rendering::camera::screen_ray;
+use crate::settings::Settings;
+use crate::world::{
+    chunk_position_to_index, queue_voxel, ClearWorld, WorldUpdates, CHUNK_COUNT, CHUNK_SIZE,
+};
+use crate::world::draw_type::ModelType;
+use crate::world::integrity::VoxelsRemoved;
+use crate::world::voxel_world::{is_air, VoxelWorld};
 
-const EDIT_RAYCAST_DIST: u32 = 100;
+/// Voxel picking steps through single voxels instead of whole tiles, so it needs to go further than
+/// input.edit_raycast_dist. This is the same distance the shader uses for the first ray.
+const VOXEL_RAYCAST_DIST: u32 = 400;
 
 #[derive(PartialEq)]
 enum WorldChange {
@@ -12,74 +24,256 @@ enum WorldChange {
     Create,
 }
 
-pub fn edit_world(
+#[derive(PartialEq, Clone, Copy)]
+pub enum SelectionMode {
+    /// Edits add and remove whole tiles from the tilemap.
+    Tile,
+    /// Edits add and remove single voxels.
+    Voxel,
+}
+
+/// What the cursor is currently pointing at. This is what edit_world changes, and it is sent to the
+/// gpu every frame so that it can be highlighted.
+pub struct Selection {
+    pub mode: SelectionMode,
+    /// In tile coordinates when in tile mode, and voxel coordinates otherwise.
+    pub hovered: Option<Hit>,
+    /// The first corner of a box selection, set by pressing B. While this is set, the selection
+    /// covers everything between it and the hovered position.
+    pub anchor: Option<IVec3>,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Selection {
+            mode: SelectionMode::Tile,
+            hovered: None,
+            anchor: None,
+        }
+    }
+}
+
+impl Selection {
+    /// Size of one selection unit in voxels.
+    fn unit_size(&self) -> i32 {
+        match self.mode {
+            SelectionMode::Tile => CHUNK_SIZE as i32,
+            SelectionMode::Voxel => 1,
+        }
+    }
+
+    /// The corners of the selected box in selection units. min is inclusive and max is exclusive.
+    pub fn unit_bounds(&self) -> Option<(IVec3, IVec3)> {
+        let hovered = self.hovered.as_ref()?;
+
+        let anchor = self.anchor.unwrap_or(hovered.pos);
+
+        Some((anchor.min(hovered.pos), anchor.max(hovered.pos) + IVec3::ONE))
+    }
+
+    /// The corners of the selected box in voxels. min is inclusive and max is exclusive.
+    pub fn voxel_bounds(&self) -> Option<(IVec3, IVec3)> {
+        let (min, max) = self.unit_bounds()?;
+
+        Some((min * self.unit_size(), max * self.unit_size()))
+    }
+}
+
+pub fn selection_commands(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut selection: ResMut<Selection>,
+) {
+    for cmd in commands.iter().filter(|cmd| cmd.is("select")) {
+        match cmd.get_arg(0) {
+            "tile" => selection.mode = SelectionMode::Tile,
+            "voxel" => selection.mode = SelectionMode::Voxel,
+            other => console.send(cmd.error(format!("unknown selection mode {}.", other))),
+        }
+
+        selection.anchor = None;
+    }
+}
+
+/// `voxel <x> <y> <z>` prints the voxel at a position, 0 being air.
+pub fn voxel_query(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    voxel_world: Res<VoxelWorld>,
+) {
+    for cmd in commands.iter().filter(|cmd| cmd.is("voxel")) {
+        let pos = cmd
+            .parse_arg(0)
+            .and_then(|x| Ok(IVec3::new(x, cmd.parse_arg(1)?, cmd.parse_arg(2)?)));
+
+        console.send(match pos {
+            Ok(pos) => cmd.info(voxel_world.voxel_at(pos).to_string()),
+            Err(err) => cmd.error(err),
+        });
+    }
+}
+
+pub fn update_selection(
     cursor_pos: Res<MousePos>,
-    mouse_input: Res<Input<KeyCode>>,
-    gpu_data: Res<GPUData>,
+    actions: Res<ActionState>,
+    mut gpu_data: ResMut<GPUData>,
+    mut selection: ResMut<Selection>,
+    voxel_world: Res<VoxelWorld>,
+    model_holders: Query<&ModelHolder>,
+    settings: Res<Settings>,
+) {
+    let (camera_pos, rd) = screen_ray(&gpu_data, cursor_pos.0);
+    let tile_dist = settings.input.edit_raycast_dist;
+
+    selection.hovered = match selection.mode {
+        SelectionMode::Tile => model_holders
+            .iter()
+            .find_map(|model_holder| match model_holder {
+                ModelHolder::Tiled { filled_spots, .. } => Some(filled_spots),
+                _ => None,
+            })
+            .and_then(|filled_spots| {
+                get_pointed_to_tile(&camera_pos, &rd, tile_dist, filled_spots)
+            }),
+        SelectionMode::Voxel => get_pointed_to_voxel(&camera_pos, &rd, &voxel_world),
+    };
+
+    if actions.just_pressed(Action::BoxSelect) {
+        selection.anchor = match selection.anchor {
+            Some(_) => None,
+            None => selection.hovered.as_ref().map(|hit| hit.pos),
+        };
+    }
+
+    match (selection.voxel_bounds(), &selection.hovered) {
+        (Some((min, max)), Some(hit)) => {
+            gpu_data.selection_min = [min.x as f32, min.y as f32, min.z as f32, 1.0];
+            gpu_data.selection_max = [max.x as f32, max.y as f32, max.z as f32, 0.0];
+            gpu_data.selection_normal = [
+                hit.normal.x as f32,
+                hit.normal.y as f32,
+                hit.normal.z as f32,
+                0.0,
+            ];
+        }
+        _ => gpu_data.selection_min[3] = 0.0,
+    }
+}
+
+pub fn edit_world(
+    actions: Res<ActionState>,
+    selection: Res<Selection>,
+    voxel_world: Res<VoxelWorld>,
+    mut world_updates: ResMut<WorldUpdates>,
     mut clear_world_events: EventWriter<ClearWorld>,
+    mut removed_events: EventWriter<VoxelsRemoved>,
     mut background_models: Query<(&mut ModelHolder, &mut Background)>,
 ) {
-    let world_change = if mouse_input.just_pressed(KeyCode::R) {
+    let world_change = if actions.just_pressed(Action::RemoveVoxel) {
         WorldChange::Delete
-    } else if mouse_input.just_pressed(KeyCode::F) {
+    } else if actions.just_pressed(Action::PlaceVoxel) {
         WorldChange::Create
     } else {
         return;
     };
 
-    let camera_data = Vec3::from_slice(&gpu_data.pos);
-    let camera_dir = Vec3::from_slice(&gpu_data.dir);
+    let hit = match &selection.hovered {
+        Some(hit) => hit,
+        None => return,
+    };
+
+    // with a box selected, the whole box is changed. otherwise, new things are placed against the
+    // face that is being pointed at.
+    let (min, max) = match (&world_change, selection.anchor) {
+        (WorldChange::Create, None) => (hit.pos + hit.normal, hit.pos + hit.normal + IVec3::ONE),
+        _ => selection.unit_bounds().unwrap(),
+    };
+
+    match selection.mode {
+        SelectionMode::Tile => {
+            for (mut model, mut background) in background_models.iter_mut() {
+                if let ModelHolder::Tiled { ref mut filled_spots, .. } = *model {
+                    for_each_in_box(min, max, |pos| {
+                        if !is_tile_in_bounds(pos) {
+                            return;
+                        }
 
-    for (mut model, mut background) in background_models.iter_mut() {
-        if let ModelHolder::Tiled { ref mut filled_spots, .. } = *model {
-            let possible_hit = get_pointed_to_tile(&cursor_pos.0, &camera_data, &camera_dir, &filled_spots);
+                        filled_spots[chunk_position_to_index(pos.as_uvec3())] =
+                            world_change == WorldChange::Create;
+                    });
 
-            if let Some(hit) = possible_hit {
-                match world_change {
-                    WorldChange::Delete => {
-                        filled_spots[hit.index] = false;
+                    if world_change == WorldChange::Delete {
                         clear_world_events.send(ClearWorld);
                     }
-                    WorldChange::Create => {
-                        let create_pos = hit.pos + hit.normal;
-                        let create_pos_uvec = UVec3::new(
-                            create_pos.x as u32,
-                            create_pos.y as u32,
-                            create_pos.z as u32
-                        );
-                        filled_spots[chunk_position_to_index(create_pos_uvec)] = true;
-                    }
+
+                    background.has_been_drawn = false;
                 }
+            }
+        }
+        SelectionMode::Voxel => {
+            let new_voxel = match world_change {
+                WorldChange::Delete => 0,
+                WorldChange::Create => voxel_world.voxel_at(hit.pos),
+            };
 
-                background.has_been_drawn = false;
+            for_each_in_box(min, max, |pos| {
+                queue_voxel(
+                    &mut world_updates,
+                    &voxel_world,
+                    pos,
+                    new_voxel,
+                    ModelType::Background,
+                );
+            });
+
+            // anything that was only held up by these voxels falls down.
+            if world_change == WorldChange::Delete {
+                removed_events.send(VoxelsRemoved { min, max });
+            }
+        }
+    }
+}
+
+fn for_each_in_box(min: IVec3, max: IVec3, mut f: impl FnMut(IVec3)) {
+    for z in min.z..max.z {
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                f(IVec3::new(x, y, z));
             }
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct Hit {
-    pub index: usize,
     pub pos: IVec3,
     pub normal: IVec3,
 }
 
-const FOV: f32 = 1.0;
+fn is_tile_in_bounds(pos: IVec3) -> bool {
+    VoxelWorld::in_bounds(pos * CHUNK_SIZE as i32)
+}
 
 fn get_pointed_to_tile(
-    cursor_pos: &Vec2,
     camera_pos: &Vec3,
-    camera_dir: &Vec3,
+    rd: &Vec3,
+    dist: u32,
     tile_map: &[bool; CHUNK_COUNT],
 ) -> Option<Hit> {
-    let rd = Vec3::new(
-                camera_dir.x.cos() * cursor_pos.x - camera_dir.x.sin() * FOV,
-                - cursor_pos.y,
-                camera_dir.x.sin() * cursor_pos.x + camera_dir.x.cos() * FOV
-            ).normalize();
     let ro = *camera_pos * (1.0 / CHUNK_SIZE as f32);
 
-    // 3D raycasting DDA algorithm
+    cast_ray(ro, *rd, dist, |pos| {
+        is_tile_in_bounds(pos) && tile_map[chunk_position_to_index(pos.as_uvec3())]
+    })
+}
+
+fn get_pointed_to_voxel(camera_pos: &Vec3, rd: &Vec3, voxel_world: &VoxelWorld) -> Option<Hit> {
+    cast_ray(*camera_pos, *rd, VOXEL_RAYCAST_DIST, |pos| !is_air(voxel_world.voxel_at(pos)))
+}
 
+/// 3D raycasting DDA algorithm, the same one used in hit_in_direction.glsl. Returns the first grid
+/// cell that is_filled returns true for.
+fn cast_ray(ro: Vec3, rd: Vec3, dist: u32, is_filled: impl Fn(IVec3) -> bool) -> Option<Hit> {
     let mut check_point = ro.floor();
 
     let xy = rd.x / rd.y;
@@ -99,30 +293,18 @@ fn get_pointed_to_tile(
     let mut ray_length = (step * (check_point - ro) + (step / 2.0 + 0.5)) * ray_unit_step_size;
 
     let mut comp = Vec3::default();
-    for _ in 0..EDIT_RAYCAST_DIST {
+    for _ in 0..dist {
         comp.x = bool_to_f32(ray_length.x < ray_length.y && ray_length.x <= ray_length.z);
         comp.y = bool_to_f32(ray_length.y < ray_length.z && ray_length.y <= ray_length.x);
         comp.z = bool_to_f32(ray_length.z < ray_length.x && ray_length.z <= ray_length.y);
 
         check_point += comp * step;
 
-        let check_point_floored = UVec3::new(check_point.x as u32, check_point.y as u32, check_point.z as u32);
-        let check_point_index = chunk_position_to_index(check_point_floored);
-
-        if check_point_index > tile_map.len() {
-            return None;
-        }
-
-        let unit_at_check_point = tile_map[check_point_index];
+        let check_point_floored = check_point.as_ivec3();
 
-        if unit_at_check_point == true {
+        if is_filled(check_point_floored) {
             return Some(Hit {
-                index: check_point_index,
-                pos: IVec3::new(
-                    check_point_floored.x as i32,
-                    check_point_floored.y as i32,
-                    check_point_floored.z as i32,
-                ),
+                pos: check_point_floored,
                 normal: IVec3::new(
                     ( - comp.x * step.x) as i32,
                     ( - comp.y * step.y) as i32,
@@ -143,4 +325,4 @@ fn bool_to_f32(input: bool) -> f32 {
     } else {
         0.0
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,632 @@
+// Systems read what the player wants to do as actions, like MoveForward or PlaceVoxel, instead of
+// reading keys directly, so that they can be rebound and driven by a gamepad. Each action can be
+// bound to any number of keys, mouse buttons, gamepad buttons and halves of gamepad axes. The
+// defaults can be replaced per action in settings.toml, like
+//
+//     [input.bindings]
+//     move_forward = ["key W", "axis LeftStickY+"]
+//     place_voxel = ["key F", "mouse Right", "button RightTrigger2"]
+//     screenshot_post = ["key shift+F12"]
+//
+// Gamepads are read through gilrs, whose events bevy's GilrsPlugin turns into gamepad input each
+// time the runner updates the app. The console still reads the keyboard directly, since it needs
+// every key. While it is open, key bindings are ignored, so typing doesn't move the camera.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use bevy::input::gamepad::{
+    GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType, Gamepads,
+};
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::input::KeyboardInputState;
+use crate::settings::Settings;
+
+/// Stick positions closer to the middle than this count as 0, so that sticks that don't quite
+/// center don't drift the camera.
+const AXIS_DEADZONE: f32 = 0.15;
+
+/// How far an action has to be pressed to count as pressed, for analog bindings.
+const PRESS_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    /// Jumps, in play mode.
+    MoveUp,
+    /// Crouches, in play mode.
+    MoveDown,
+    /// Moves at input.fast_move_speed instead of input.move_speed, or runs in play mode.
+    MoveFast,
+    TurnLeft,
+    TurnRight,
+    TiltUp,
+    TiltDown,
+    RollLeft,
+    RollRight,
+    ZoomIn,
+    ZoomOut,
+    PlaceVoxel,
+    RemoveVoxel,
+    /// Starts or clears a box selection at what the cursor is pointing at.
+    BoxSelect,
+    ToggleMouseLook,
+    ToggleStats,
+    ToggleFullscreen,
+    Screenshot,
+    ScreenshotPost,
+    /// Moves the frame seed on by one, to see the noise change.
+    StepTime,
+}
+
+impl Action {
+    pub const ALL: [Action; 24] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveFast,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::TiltUp,
+        Action::TiltDown,
+        Action::RollLeft,
+        Action::RollRight,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::PlaceVoxel,
+        Action::RemoveVoxel,
+        Action::BoxSelect,
+        Action::ToggleMouseLook,
+        Action::ToggleStats,
+        Action::ToggleFullscreen,
+        Action::Screenshot,
+        Action::ScreenshotPost,
+        Action::StepTime,
+    ];
+
+    /// The name used in input.bindings, like move_forward.
+    pub fn name(self) -> String {
+        let mut name = String::new();
+
+        for c in format!("{:?}", self).chars() {
+            if c.is_uppercase() && !name.is_empty() {
+                name.push('_');
+            }
+
+            name.push(c.to_ascii_lowercase());
+        }
+
+        name
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    /// Only counts while either shift is held.
+    ShiftKey(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    /// One half of an axis. positive picks which half.
+    GamepadAxis {
+        axis: GamepadAxisType,
+        positive: bool,
+    },
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "key {:?}", key),
+            Binding::ShiftKey(key) => write!(f, "key shift+{:?}", key),
+            Binding::Mouse(button) => write!(f, "mouse {:?}", button),
+            Binding::GamepadButton(button) => write!(f, "button {:?}", button),
+            Binding::GamepadAxis { axis, positive } => {
+                write!(f, "axis {:?}{}", axis, if *positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+impl Binding {
+    /// Reads a binding written like `key W`, `key shift+F12`, `mouse Left`, `button South` or
+    /// `axis LeftStickY+`.
+    fn parse(text: &str) -> Result<Binding, String> {
+        let (kind, name) = text
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| format!("expected a kind of binding and a name, got {}", text))?;
+        let name = name.trim();
+
+        let unknown = || format!("unknown {} {}", kind, name);
+
+        match kind {
+            "key" => match name.strip_prefix("shift+") {
+                Some(name) => find_by_name(KEYS, name).map(Binding::ShiftKey),
+                None => find_by_name(KEYS, name).map(Binding::Key),
+            }
+            .ok_or_else(unknown),
+            "mouse" => find_by_name(MOUSE_BUTTONS, name)
+                .map(Binding::Mouse)
+                .ok_or_else(unknown),
+            "button" => find_by_name(GAMEPAD_BUTTONS, name)
+                .map(Binding::GamepadButton)
+                .ok_or_else(unknown),
+            "axis" => {
+                let (axis, positive) = match name.strip_suffix('+') {
+                    Some(axis) => (axis, true),
+                    None => (
+                        name.strip_suffix('-').ok_or_else(|| {
+                            format!("expected + or - after the axis, got {}", name)
+                        })?,
+                        false,
+                    ),
+                };
+
+                find_by_name(GAMEPAD_AXES, axis)
+                    .map(|axis| Binding::GamepadAxis { axis, positive })
+                    .ok_or_else(unknown)
+            }
+            _ => Err(format!("expected key, mouse, button or axis, got {}", kind)),
+        }
+    }
+}
+
+/// Which bindings each action has, see update_input_map.
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Action::*;
+        use GamepadAxisType::*;
+        use GamepadButtonType::{
+            DPadDown, DPadUp, East, LeftThumb, LeftTrigger, LeftTrigger2, RightTrigger,
+            RightTrigger2, Select, South, West,
+        };
+        use KeyCode::*;
+
+        let key = Binding::Key;
+        let left_click = Binding::Mouse(MouseButton::Left);
+        let right_click = Binding::Mouse(MouseButton::Right);
+        let button = Binding::GamepadButton;
+        let axis = |axis, positive| Binding::GamepadAxis { axis, positive };
+
+        let bindings = [
+            (MoveForward, vec![key(W), axis(LeftStickY, true)]),
+            (MoveBack, vec![key(S), axis(LeftStickY, false)]),
+            (MoveLeft, vec![key(A), axis(LeftStickX, false)]),
+            (MoveRight, vec![key(D), axis(LeftStickX, true)]),
+            (MoveUp, vec![key(Space), button(South)]),
+            (MoveDown, vec![key(LShift), button(East)]),
+            (MoveFast, vec![key(Return), button(LeftThumb)]),
+            (TurnLeft, vec![key(Q), axis(RightStickX, false)]),
+            (TurnRight, vec![key(E), axis(RightStickX, true)]),
+            (TiltUp, vec![key(H), axis(RightStickY, true)]),
+            (TiltDown, vec![key(G), axis(RightStickY, false)]),
+            (RollLeft, vec![key(T), button(LeftTrigger)]),
+            (RollRight, vec![key(Y), button(RightTrigger)]),
+            (ZoomIn, vec![key(Z), button(DPadUp)]),
+            (ZoomOut, vec![key(X), button(DPadDown)]),
+            (PlaceVoxel, vec![key(F), right_click, button(RightTrigger2)]),
+            (RemoveVoxel, vec![key(R), left_click, button(LeftTrigger2)]),
+            (BoxSelect, vec![key(B), button(West)]),
+            (ToggleMouseLook, vec![key(M)]),
+            (ToggleStats, vec![key(F3), button(Select)]),
+            (ToggleFullscreen, vec![key(F11)]),
+            (Screenshot, vec![key(F12)]),
+            (ScreenshotPost, vec![Binding::ShiftKey(F12)]),
+            (StepTime, vec![key(P)]),
+        ];
+
+        InputMap {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl InputMap {
+    /// The defaults, with the actions in input.bindings bound to what they list instead. Returns
+    /// what couldn't be read too, those actions keep their defaults.
+    pub fn from_settings(bindings: &BTreeMap<String, Vec<String>>) -> (Self, Vec<String>) {
+        let mut input_map = InputMap::default();
+        let mut errors = Vec::new();
+
+        for (name, bindings) in bindings {
+            if let Err(err) = input_map.rebind(name, bindings) {
+                errors.push(format!("input.bindings.{}: {}.", name, err));
+            }
+        }
+
+        (input_map, errors)
+    }
+
+    fn rebind(&mut self, name: &str, bindings: &[String]) -> Result<(), String> {
+        let action = Action::from_name(name).ok_or_else(|| format!("unknown action {}", name))?;
+
+        // an action can be left unbound with an empty list.
+        let bindings = bindings
+            .iter()
+            .map(|binding| Binding::parse(binding))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.bindings.insert(action, bindings);
+
+        Ok(())
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// How far each action is pressed this frame, from 0 to 1. Buttons are either 0 or 1, and axes can
+/// be anywhere in between.
+#[derive(Default)]
+pub struct ActionState {
+    values: HashMap<Action, f32>,
+    last_values: HashMap<Action, f32>,
+}
+
+impl ActionState {
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action) >= PRESS_THRESHOLD
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        let last_value = self.last_values.get(&action).copied().unwrap_or(0.0);
+
+        self.pressed(action) && last_value < PRESS_THRESHOLD
+    }
+
+    /// positive minus negative, from -1 to 1.
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        self.value(positive) - self.value(negative)
+    }
+}
+
+/// Rebuilds the bindings whenever the settings change, so that `settings load` picks up edits to
+/// input.bindings without restarting.
+pub fn update_input_map(
+    settings: Res<Settings>,
+    mut input_map: ResMut<InputMap>,
+    mut console: EventWriter<ConsoleMessage>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    let (new_map, errors) = InputMap::from_settings(&settings.input.bindings);
+
+    *input_map = new_map;
+
+    for err in errors {
+        console.send(ConsoleMessage::error(err));
+    }
+}
+
+/// Runs after bevy has updated its input resources, so actions are up to date for the update.
+#[allow(clippy::too_many_arguments)]
+pub fn update_actions(
+    input_map: Res<InputMap>,
+    input_state: Res<KeyboardInputState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<ActionState>,
+) {
+    let keys_enabled = *input_state != KeyboardInputState::Commands;
+    let shift = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+
+    let binding_value = |binding: &Binding| match *binding {
+        Binding::Key(key) => bool_value(keys_enabled && keyboard_input.pressed(key)),
+        Binding::ShiftKey(key) => bool_value(keys_enabled && shift && keyboard_input.pressed(key)),
+        Binding::Mouse(button) => bool_value(mouse_input.pressed(button)),
+        Binding::GamepadButton(button) => bool_value(
+            gamepads
+                .iter()
+                .any(|gamepad| gamepad_buttons.pressed(GamepadButton(*gamepad, button))),
+        ),
+        Binding::GamepadAxis { axis, positive } => gamepads
+            .iter()
+            .filter_map(|gamepad| gamepad_axes.get(GamepadAxis(*gamepad, axis)))
+            .map(|value| if positive { value } else { -value })
+            .map(|value| ((value - AXIS_DEADZONE) / (1.0 - AXIS_DEADZONE)).clamp(0.0, 1.0))
+            .fold(0.0, f32::max),
+    };
+
+    let values = Action::ALL
+        .iter()
+        .map(|action| {
+            let value = input_map
+                .bindings(*action)
+                .iter()
+                .map(binding_value)
+                .fold(0.0, f32::max);
+
+            (*action, value)
+        })
+        .collect();
+
+    actions.last_values = std::mem::replace(&mut actions.values, values);
+}
+
+fn bool_value(pressed: bool) -> f32 {
+    if pressed {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn find_by_name<T: fmt::Debug + Copy>(all: &[T], name: &str) -> Option<T> {
+    all.iter()
+        .copied()
+        .find(|value| format!("{:?}", value).eq_ignore_ascii_case(name))
+}
+
+/// The keys that can be named in input.bindings.
+const KEYS: &[KeyCode] = {
+    use KeyCode::*;
+
+    &[
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        Key0,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Escape,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        Insert,
+        Home,
+        Delete,
+        End,
+        PageDown,
+        PageUp,
+        Left,
+        Up,
+        Right,
+        Down,
+        Back,
+        Return,
+        Space,
+        Tab,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadAdd,
+        NumpadSubtract,
+        NumpadEnter,
+        Apostrophe,
+        Backslash,
+        Comma,
+        Equals,
+        Grave,
+        LBracket,
+        Minus,
+        Period,
+        RBracket,
+        Semicolon,
+        Slash,
+        LAlt,
+        LControl,
+        LShift,
+        RAlt,
+        RControl,
+        RShift,
+    ]
+};
+
+const MOUSE_BUTTONS: &[MouseButton] = &[MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+const GAMEPAD_BUTTONS: &[GamepadButtonType] = {
+    use GamepadButtonType::*;
+
+    &[
+        South,
+        East,
+        North,
+        West,
+        C,
+        Z,
+        LeftTrigger,
+        LeftTrigger2,
+        RightTrigger,
+        RightTrigger2,
+        Select,
+        Start,
+        Mode,
+        LeftThumb,
+        RightThumb,
+        DPadUp,
+        DPadDown,
+        DPadLeft,
+        DPadRight,
+    ]
+};
+
+const GAMEPAD_AXES: &[GamepadAxisType] = {
+    use GamepadAxisType::*;
+
+    &[
+        LeftStickX,
+        LeftStickY,
+        LeftZ,
+        RightStickX,
+        RightStickY,
+        RightZ,
+        DPadX,
+        DPadY,
+    ]
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_names_round_trip() {
+        for action in Action::ALL {
+            assert_eq!(Action::from_name(&action.name()), Some(action));
+        }
+
+        assert_eq!(Action::MoveForward.name(), "move_forward");
+        assert_eq!(Action::ToggleMouseLook.name(), "toggle_mouse_look");
+    }
+
+    #[test]
+    fn default_bindings_round_trip() {
+        let input_map = InputMap::default();
+
+        for action in Action::ALL {
+            for binding in input_map.bindings(action) {
+                assert_eq!(Binding::parse(&binding.to_string()), Ok(*binding));
+            }
+        }
+    }
+
+    #[test]
+    fn parses_bindings() {
+        assert_eq!(Binding::parse("key W"), Ok(Binding::Key(KeyCode::W)));
+        assert_eq!(
+            Binding::parse(" key shift+f12 "),
+            Ok(Binding::ShiftKey(KeyCode::F12))
+        );
+        assert_eq!(
+            Binding::parse("mouse Right"),
+            Ok(Binding::Mouse(MouseButton::Right))
+        );
+        assert_eq!(
+            Binding::parse("button South"),
+            Ok(Binding::GamepadButton(GamepadButtonType::South))
+        );
+        assert_eq!(
+            Binding::parse("axis LeftStickY-"),
+            Ok(Binding::GamepadAxis {
+                axis: GamepadAxisType::LeftStickY,
+                positive: false,
+            })
+        );
+    }
+
+    #[test]
+    fn bad_bindings_say_what_was_wrong() {
+        assert_eq!(
+            Binding::parse("W"),
+            Err("expected a kind of binding and a name, got W".to_string())
+        );
+        assert_eq!(
+            Binding::parse("key Nope"),
+            Err("unknown key Nope".to_string())
+        );
+        assert_eq!(
+            Binding::parse("axis LeftStickY"),
+            Err("expected + or - after the axis, got LeftStickY".to_string())
+        );
+        assert_eq!(
+            Binding::parse("pedal Left"),
+            Err("expected key, mouse, button or axis, got pedal".to_string())
+        );
+    }
+
+    #[test]
+    fn settings_replace_only_the_actions_they_name() {
+        let bindings = BTreeMap::from([
+            ("move_forward".to_string(), vec!["key Up".to_string()]),
+            ("step_time".to_string(), Vec::new()),
+            ("fly".to_string(), vec!["key F".to_string()]),
+            ("zoom_in".to_string(), vec!["key Nope".to_string()]),
+        ]);
+
+        let (input_map, errors) = InputMap::from_settings(&bindings);
+
+        assert_eq!(
+            input_map.bindings(Action::MoveForward),
+            [Binding::Key(KeyCode::Up)]
+        );
+        assert!(input_map.bindings(Action::StepTime).is_empty());
+        assert_eq!(
+            input_map.bindings(Action::ZoomIn),
+            InputMap::default().bindings(Action::ZoomIn)
+        );
+        assert_eq!(
+            errors,
+            [
+                "input.bindings.fly: unknown action fly.",
+                "input.bindings.zoom_in: unknown key Nope.",
+            ]
+        );
+    }
+}
@@ -1,5 +1,16 @@
+pub mod actions;
+pub mod player;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{AddCommands, ArgSpec, CommandSpec, ON_OFF};
+use crate::debug::Command;
+use crate::input::actions::{Action, ActionState, InputMap};
+use crate::input::player::MovementMode;
+use crate::rendering::camera::Camera;
+use crate::settings::Settings;
 use crate::GPUData;
-use bevy::prelude::KeyCode::*;
+use bevy::input::mouse::MouseMotion;
+use bevy::window::{CursorEntered, CursorLeft, CursorMoved, ReceivedCharacter, WindowFocused};
 use bevy::prelude::*;
 
 #[derive(Default)]
@@ -8,10 +19,26 @@ pub struct CtklrInputPlugin;
 impl Plugin for CtklrInputPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(camera_movement)
+            .add_system(mouse_look)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                actions::update_actions.after(bevy::input::InputSystem),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, actions::update_input_map)
+            // bevy's WindowPlugin isn't used, so the window events the runner sends are added here.
+            .add_event::<ReceivedCharacter>()
+            .add_event::<CursorMoved>()
+            .add_event::<CursorEntered>()
+            .add_event::<CursorLeft>()
+            .add_event::<WindowFocused>()
+            .insert_resource(MouseLook::default())
             .insert_resource(KeyboardInputState::default())
-            .insert_resource(CursorInputState::TilemapEdit)
-            .insert_resource(GamepadInputState::Play)
-            .insert_resource(MousePos(Vec2::new(0.0, 0.0)));
+            .insert_resource(InputMap::default())
+            .insert_resource(ActionState::default())
+            .insert_resource(MousePos(Vec2::new(0.0, 0.0)))
+            .add_plugin(player::PlayerPlugin)
+            .add_commands(COMMANDS)
+            .add_commands(player::COMMANDS);
     }
 }
 
@@ -27,71 +54,100 @@ impl Default for KeyboardInputState {
     }
 }
 
-#[derive(PartialEq)]
-pub enum CursorInputState {
-    TilemapEdit,
-    Play,
-}
+pub struct MousePos(pub Vec2);
 
-#[derive(PartialEq)]
-pub enum GamepadInputState {
-    Play,
+const COMMANDS: &[CommandSpec] = &[CommandSpec {
+    name: "mouse_look",
+    description: "turns the camera with the mouse, also toggled with M.",
+    args: &[ArgSpec::new("enabled", ON_OFF)],
+}];
+
+/// Turns the camera when the mouse moves. Toggled with M (toggle_mouse_look), or
+/// `mouse_look on|off`. How far it turns is input.mouse_sensitivity in the settings. While it is
+/// on, the runner grabs and hides the cursor.
+#[derive(Default)]
+pub struct MouseLook {
+    pub enabled: bool,
 }
 
-pub struct MousePos(pub Vec2);
+fn mouse_look(
+    actions: Res<ActionState>,
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_look: ResMut<MouseLook>,
+    mut camera: ResMut<Camera>,
+    settings: Res<Settings>,
+) {
+    if actions.just_pressed(Action::ToggleMouseLook) {
+        mouse_look.enabled = !mouse_look.enabled;
+    }
+
+    for cmd in commands.iter().filter(|cmd| cmd.is("mouse_look")) {
+        match cmd.get_arg(0) {
+            "on" => mouse_look.enabled = true,
+            "off" => mouse_look.enabled = false,
+            other => console.send(cmd.error(format!("expected on or off, got {}.", other))),
+        }
+    }
+
+    let delta: Vec2 = mouse_motion.iter().map(|motion| motion.delta).sum();
 
+    if !mouse_look.enabled || delta == Vec2::ZERO {
+        return;
+    }
+
+    let sensitivity = settings.input.mouse_sensitivity as f32;
+
+    camera.turn(delta.x * sensitivity);
+    camera.tilt(-delta.y * sensitivity);
+}
+
+/// Flies the camera around. Gamepad sticks move it as far as they are pushed, so the speed can be
+/// anything up to the full move speed. In play mode the character moves the camera, so only
+/// looking around is done here.
 fn camera_movement(
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<ActionState>,
     input_state: Res<KeyboardInputState>,
+    mode: Res<MovementMode>,
+    mut camera: ResMut<Camera>,
     mut gpu_data: ResMut<GPUData>,
+    settings: Res<Settings>,
 ) {
     if *input_state != KeyboardInputState::FreeCam {
         return;
     }
 
-    let mut move_speed = 0.02;
+    let move_speed = if actions.pressed(Action::MoveFast) {
+        settings.input.fast_move_speed as f32
+    } else {
+        settings.input.move_speed as f32
+    };
 
-    if keyboard_input.pressed(Return) {
-        move_speed = 1.0;
-    }
-
-    if keyboard_input.pressed(A) {
-        gpu_data.pos[0] -= move_speed;
-    } else if keyboard_input.pressed(D) {
-        gpu_data.pos[0] += move_speed;
-    }
+    // moving is relative to where the camera is looking, except for up and down, which always
+    // follow the world so that flying over the ground is easy.
+    if *mode == MovementMode::Fly {
+        let movement = camera.right() * actions.axis(Action::MoveLeft, Action::MoveRight)
+            + Vec3::Y * actions.axis(Action::MoveDown, Action::MoveUp)
+            + camera.forward() * actions.axis(Action::MoveBack, Action::MoveForward);
 
-    if keyboard_input.pressed(LShift) {
-        gpu_data.pos[1] -= move_speed;
-    } else if keyboard_input.pressed(Space) {
-        gpu_data.pos[1] += move_speed;
-    }
+        camera.position += movement * move_speed;
 
-    if keyboard_input.pressed(S) {
-        gpu_data.pos[2] -= move_speed;
-    } else if keyboard_input.pressed(W) {
-        gpu_data.pos[2] += move_speed;
+        camera.roll(actions.axis(Action::RollLeft, Action::RollRight) * move_speed);
     }
 
-    if keyboard_input.pressed(T) {
-        gpu_data.dir[2] -= move_speed;
-    } else if keyboard_input.pressed(Y) {
-        gpu_data.dir[2] += move_speed;
-    }
+    camera.tilt(actions.axis(Action::TiltDown, Action::TiltUp) * move_speed);
+    camera.turn(actions.axis(Action::TurnLeft, Action::TurnRight) * move_speed);
 
-    if keyboard_input.pressed(G) {
-        gpu_data.dir[1] -= move_speed;
-    } else if keyboard_input.pressed(H) {
-        gpu_data.dir[1] += move_speed;
-    }
+    let zoom = actions.axis(Action::ZoomOut, Action::ZoomIn);
 
-    if keyboard_input.pressed(Q) {
-        gpu_data.dir[0] -= move_speed;
-    } else if keyboard_input.pressed(E) {
-        gpu_data.dir[0] += move_speed;
+    if zoom > 0.0 {
+        camera.zoom(1.0 + move_speed * zoom);
+    } else if zoom < 0.0 {
+        camera.zoom(1.0 / (1.0 - move_speed * zoom));
     }
 
-    if keyboard_input.just_pressed(P) {
+    if actions.just_pressed(Action::StepTime) {
         gpu_data.time += 1;
         println!("{}", gpu_data.time);
     }
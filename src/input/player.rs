@@ -0,0 +1,449 @@
+// Play mode, where the camera is the eyes of a character that walks around the voxel world instead
+// of flying through it. `mode play` drops the character in where the camera is, and `mode fly` goes
+// back to the free camera. The same actions drive both: move_up jumps, move_down crouches and
+// move_fast runs. Looking around is still done by camera_movement and mouse_look.
+//
+// The character is a box standing on its feet, that is moved one axis at a time on the physics
+// timestep and stopped by any voxel that isn't air. The bottom of the world counts as solid, so
+// there is always something to stand on.
+
+use bevy::core::FixedTimestep;
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::input::actions::{Action, ActionState};
+use crate::rendering::camera::Camera;
+use crate::settings::{PlayerSettings, Settings};
+use crate::world::voxel_world::{is_air, VoxelWorld};
+use crate::PHYSICS_TIME_STEP;
+
+/// The fastest the character can fall, in voxels per second. It also keeps each physics step
+/// shorter than a voxel, so that nothing is fallen through.
+const MAX_FALL_SPEED: f32 = 50.0;
+/// How far below the top of the character its eyes are.
+const EYE_OFFSET: f32 = 1.0;
+/// Keeps boxes that are exactly touching a voxel from counting as inside of it.
+const EPSILON: f32 = 0.001;
+
+pub const COMMANDS: &[CommandSpec] = &[CommandSpec {
+    name: "mode",
+    description: "walks around as a character with play, or flies with the free camera with fly.",
+    args: &[ArgSpec::new("mode", ArgKind::Enum(&["play", "fly"]))],
+}];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementMode {
+    /// The free camera, which goes through everything.
+    Fly,
+    /// Walking, with gravity and collision.
+    Play,
+}
+
+impl Default for MovementMode {
+    fn default() -> Self {
+        MovementMode::Fly
+    }
+}
+
+#[derive(Default)]
+pub struct Player {
+    /// The middle of the bottom of the character's box.
+    pub position: Vec3,
+    /// In voxels per second.
+    pub velocity: Vec3,
+    pub on_ground: bool,
+    pub crouching: bool,
+}
+
+impl Player {
+    fn height(&self, settings: &PlayerSettings) -> f32 {
+        if self.crouching {
+            settings.crouch_height as f32
+        } else {
+            settings.height as f32
+        }
+    }
+
+    fn eye_height(&self, settings: &PlayerSettings) -> f32 {
+        (self.height(settings) - EYE_OFFSET).max(0.0)
+    }
+}
+
+#[derive(Default)]
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MovementMode::default())
+            .insert_resource(Player::default())
+            .add_system(mode_command)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::step(PHYSICS_TIME_STEP))
+                    .with_system(player_physics),
+            );
+    }
+}
+
+/// `mode play|fly`.
+fn mode_command(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut mode: ResMut<MovementMode>,
+    mut player: ResMut<Player>,
+    mut camera: ResMut<Camera>,
+    settings: Res<Settings>,
+) {
+    for cmd in commands.iter().filter(|cmd| cmd.is("mode")) {
+        match cmd.get_arg(0) {
+            "play" => {
+                // the character is put wherever its eyes would be where the camera is.
+                *player = Player::default();
+                player.position = camera.position - Vec3::Y * player.eye_height(&settings.player);
+                // rolling is only done in fly mode, so it would be stuck otherwise.
+                camera.remove_roll();
+                *mode = MovementMode::Play;
+            }
+            "fly" => *mode = MovementMode::Fly,
+            other => console.send(cmd.error(format!("expected play or fly, got {}.", other))),
+        }
+    }
+}
+
+fn player_physics(
+    mode: Res<MovementMode>,
+    actions: Res<ActionState>,
+    voxel_world: Res<VoxelWorld>,
+    settings: Res<Settings>,
+    mut player: ResMut<Player>,
+    mut camera: ResMut<Camera>,
+) {
+    if *mode != MovementMode::Play {
+        return;
+    }
+
+    let settings = &settings.player;
+    let voxel_at = |pos| voxel_world.voxel_at(pos);
+    let dt = PHYSICS_TIME_STEP as f32;
+    let half_width = settings.width as f32 / 2.0;
+
+    // crouching is easy, but standing back up needs room above the character.
+    let wants_to_crouch = actions.pressed(Action::MoveDown);
+
+    if player.crouching && !wants_to_crouch {
+        let standing = bounds(player.position, half_width, settings.height as f32);
+
+        if !collides(&voxel_at, standing) {
+            player.crouching = false;
+        }
+    } else if wants_to_crouch {
+        player.crouching = true;
+    }
+
+    let speed = if player.crouching {
+        settings.crouch_speed as f32
+    } else if actions.pressed(Action::MoveFast) {
+        settings.run_speed as f32
+    } else {
+        settings.walk_speed as f32
+    };
+
+    // walking follows where the camera is looking, but stays level.
+    let forward = flatten(camera.forward());
+    let right = flatten(camera.right());
+
+    let wish = forward * actions.axis(Action::MoveBack, Action::MoveForward)
+        + right * actions.axis(Action::MoveLeft, Action::MoveRight);
+    let wish = if wish.length() > 1.0 {
+        wish.normalize()
+    } else {
+        wish
+    };
+
+    player.velocity.x = wish.x * speed;
+    player.velocity.z = wish.z * speed;
+
+    if player.on_ground && !player.crouching && actions.pressed(Action::MoveUp) {
+        player.velocity.y = settings.jump_speed as f32;
+    }
+
+    player.velocity.y = (player.velocity.y - settings.gravity as f32 * dt).max(-MAX_FALL_SPEED);
+
+    let height = player.height(settings);
+    let step = player.velocity * dt;
+
+    // up and down goes first, so that on_ground is known for stepping up.
+    let blocked_y = move_along(&voxel_at, &mut player, Vec3::Y, step.y, half_width, height);
+
+    if blocked_y {
+        player.on_ground = step.y < 0.0;
+        player.velocity.y = 0.0;
+    } else {
+        player.on_ground = false;
+    }
+
+    for axis in [Vec3::X, Vec3::Z] {
+        step_along(
+            &voxel_at,
+            &mut player,
+            axis,
+            step.dot(axis),
+            half_width,
+            height,
+            settings.step_height as f32,
+        );
+    }
+
+    camera.position = player.position + Vec3::Y * player.eye_height(settings);
+}
+
+/// Moves the character along one axis, stopping it against the first voxel in the way. Returns
+/// whether it was stopped.
+fn move_along(
+    voxel_at: &impl Fn(IVec3) -> u32,
+    player: &mut Player,
+    axis: Vec3,
+    amount: f32,
+    half_width: f32,
+    height: f32,
+) -> bool {
+    if amount == 0.0 {
+        return false;
+    }
+
+    let moved = player.position + axis * amount;
+    let (min, max) = bounds(moved, half_width, height);
+
+    if !collides(voxel_at, (min, max)) {
+        player.position = moved;
+        return false;
+    }
+
+    // the voxel that was hit starts at the next whole number along the axis, so the character is
+    // put right up against it.
+    let edge = if amount > 0.0 {
+        max.dot(axis).floor() - (max - moved).dot(axis)
+    } else {
+        min.dot(axis).ceil() + (moved - min).dot(axis)
+    };
+
+    let snapped = moved + axis * (edge - moved.dot(axis));
+
+    if !collides(voxel_at, bounds(snapped, half_width, height)) {
+        player.position = snapped;
+    }
+
+    true
+}
+
+/// Moves the character along a level axis. If it is stopped while on the ground, ledges up to
+/// step_height are climbed by trying the move again from higher up. Otherwise it loses its speed
+/// along the axis.
+fn step_along(
+    voxel_at: &impl Fn(IVec3) -> u32,
+    player: &mut Player,
+    axis: Vec3,
+    amount: f32,
+    half_width: f32,
+    height: f32,
+    step_height: f32,
+) {
+    let start = player.position;
+
+    if !move_along(voxel_at, player, axis, amount, half_width, height) {
+        return;
+    }
+
+    if player.on_ground {
+        let raised = start + Vec3::Y * step_height;
+        let moved = raised + axis * amount;
+
+        if !collides(voxel_at, bounds(raised, half_width, height))
+            && !collides(voxel_at, bounds(moved, half_width, height))
+        {
+            player.position = moved;
+            return;
+        }
+    }
+
+    player.velocity -= axis * player.velocity.dot(axis);
+}
+
+fn bounds(position: Vec3, half_width: f32, height: f32) -> (Vec3, Vec3) {
+    (
+        position - Vec3::new(half_width, 0.0, half_width),
+        position + Vec3::new(half_width, height, half_width),
+    )
+}
+
+/// Whether any voxel that the box is inside of is solid.
+fn collides(voxel_at: &impl Fn(IVec3) -> u32, (min, max): (Vec3, Vec3)) -> bool {
+    let min = (min + Vec3::splat(EPSILON)).floor().as_ivec3();
+    let max = (max - Vec3::splat(EPSILON)).floor().as_ivec3();
+
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let pos = IVec3::new(x, y, z);
+
+                if pos.y < 0 || !is_air(voxel_at(pos)) {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+fn flatten(direction: Vec3) -> Vec3 {
+    Vec3::new(direction.x, 0.0, direction.z).normalize_or_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF_WIDTH: f32 = 2.0;
+    const HEIGHT: f32 = 12.0;
+    const STEP_HEIGHT: f32 = 1.0;
+
+    /// A world where only the given voxels are solid, besides the bottom of the world.
+    fn grid(solid: &[IVec3]) -> impl Fn(IVec3) -> u32 + '_ {
+        move |pos| if solid.contains(&pos) { 1 } else { 0 }
+    }
+
+    fn standing_at(position: Vec3) -> Player {
+        Player {
+            position,
+            velocity: Vec3::new(10.0, 0.0, 0.0),
+            on_ground: true,
+            crouching: false,
+        }
+    }
+
+    #[test]
+    fn moves_freely_through_air() {
+        let mut player = standing_at(Vec3::new(5.0, 3.0, 5.0));
+
+        let blocked = move_along(&grid(&[]), &mut player, Vec3::X, 0.5, HALF_WIDTH, HEIGHT);
+
+        assert!(!blocked);
+        assert_eq!(player.position, Vec3::new(5.5, 3.0, 5.0));
+    }
+
+    #[test]
+    fn lands_on_the_bottom_of_the_world() {
+        let mut player = standing_at(Vec3::new(5.0, 0.5, 5.0));
+
+        let blocked = move_along(&grid(&[]), &mut player, Vec3::Y, -0.75, HALF_WIDTH, HEIGHT);
+
+        assert!(blocked);
+        assert_eq!(player.position.y, 0.0);
+    }
+
+    #[test]
+    fn stops_right_up_against_a_wall() {
+        let wall = [IVec3::new(10, 0, 5), IVec3::new(10, 1, 5)];
+        let mut player = standing_at(Vec3::new(7.5, 0.0, 5.5));
+
+        let blocked = move_along(&grid(&wall), &mut player, Vec3::X, 0.75, HALF_WIDTH, HEIGHT);
+
+        assert!(blocked);
+        assert_eq!(player.position.x, 8.0);
+
+        let mut player = standing_at(Vec3::new(13.5, 0.0, 5.5));
+
+        let blocked = move_along(
+            &grid(&wall),
+            &mut player,
+            Vec3::X,
+            -0.75,
+            HALF_WIDTH,
+            HEIGHT,
+        );
+
+        assert!(blocked);
+        assert_eq!(player.position.x, 13.0);
+    }
+
+    #[test]
+    fn hitting_the_ceiling_stops_the_jump() {
+        let ceiling = [IVec3::new(5, 14, 5)];
+        let mut player = standing_at(Vec3::new(5.5, 1.5, 5.5));
+
+        let blocked = move_along(
+            &grid(&ceiling),
+            &mut player,
+            Vec3::Y,
+            0.75,
+            HALF_WIDTH,
+            HEIGHT,
+        );
+
+        assert!(blocked);
+        assert_eq!(player.position.y, 2.0);
+    }
+
+    #[test]
+    fn steps_up_low_ledges() {
+        let ledge = [IVec3::new(8, 0, 5)];
+        let mut player = standing_at(Vec3::new(5.5, 0.0, 5.5));
+
+        step_along(
+            &grid(&ledge),
+            &mut player,
+            Vec3::X,
+            0.75,
+            HALF_WIDTH,
+            HEIGHT,
+            STEP_HEIGHT,
+        );
+
+        assert_eq!(player.position, Vec3::new(6.25, 1.0, 5.5));
+        assert_eq!(player.velocity.x, 10.0);
+    }
+
+    #[test]
+    fn is_stopped_by_high_ledges() {
+        let ledge = [IVec3::new(8, 0, 5), IVec3::new(8, 1, 5)];
+        let mut player = standing_at(Vec3::new(5.5, 0.0, 5.5));
+
+        step_along(
+            &grid(&ledge),
+            &mut player,
+            Vec3::X,
+            0.75,
+            HALF_WIDTH,
+            HEIGHT,
+            STEP_HEIGHT,
+        );
+
+        assert_eq!(player.position, Vec3::new(6.0, 0.0, 5.5));
+        assert_eq!(player.velocity.x, 0.0);
+    }
+
+    #[test]
+    fn does_not_step_up_in_the_air() {
+        let ledge = [IVec3::new(8, 3, 5)];
+        let mut player = Player {
+            on_ground: false,
+            ..standing_at(Vec3::new(5.5, 3.0, 5.5))
+        };
+
+        step_along(
+            &grid(&ledge),
+            &mut player,
+            Vec3::X,
+            0.75,
+            HALF_WIDTH,
+            HEIGHT,
+            STEP_HEIGHT,
+        );
+
+        assert_eq!(player.position, Vec3::new(6.0, 3.0, 5.5));
+        assert_eq!(player.velocity.x, 0.0);
+    }
+}
@@ -1,11 +1,14 @@
 use bevy::{core::FixedTimestep, prelude::*};
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use debug::CtklrDebugPlugin;
 use input::CtklrInputPlugin;
 use rendering::CtklrRenderPlugin;
 use world::CtklrWorldPlugin;
 
+use crate::options::{StartupOptions, USAGE};
+use crate::settings::{CtklrSettingsPlugin, Settings};
 use crate::rendering::gpu_data::GPUData;
 use crate::world::draw_type::Background;
 use crate::world::model_type::ModelHolder;
@@ -13,7 +16,9 @@ use crate::world::CHUNK_COUNT;
 
 mod debug;
 mod input;
+mod options;
 mod rendering;
+mod settings;
 mod world;
 
 const PHYSICS_TIME_STEP: f64 = 1.0 / 60.0;
@@ -23,24 +28,52 @@ fn main() {
 
     env_logger::init();
 
+    let options = match StartupOptions::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if options.golden {
+        let passed = rendering::reference::golden::run_golden_tests(options.bless);
+
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
     App::new()
+        .insert_resource(options)
         .add_event::<rendering::render::RenderEvent>()
         .add_plugin(bevy::core::CorePlugin::default())
         .add_plugin(bevy::transform::TransformPlugin::default())
         .add_plugin(bevy::diagnostic::DiagnosticsPlugin::default())
         .add_plugin(bevy::input::InputPlugin::default())
+        .add_plugin(bevy::gilrs::GilrsPlugin::default())
         .add_plugin(bevy::asset::AssetPlugin::default())
         .add_plugin(bevy::scene::ScenePlugin::default())
+        .add_plugin(CtklrSettingsPlugin::default())
         .add_plugin(CtklrRenderPlugin::default())
         .add_plugin(CtklrDebugPlugin::default())
         .add_plugin(CtklrWorldPlugin::default())
         .add_plugin(CtklrInputPlugin::default())
-        .add_startup_system(spawn_tilemap.before("spawn"))
+        .add_startup_system(spawn_world.before("spawn"))
         .add_startup_system(change_tilemap.label("change"))
         .run();
 }
 
-fn change_tilemap(mut model_holders: Query<(&mut Background, &mut ModelHolder)>) {
+/// The tilemap is filled in at random, from the seed given with --tilemap if there is one.
+fn tilemap_rng(options: &StartupOptions) -> StdRng {
+    match options.tilemap_seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+fn change_tilemap(
+    options: Res<StartupOptions>,
+    mut model_holders: Query<(&mut Background, &mut ModelHolder)>,
+) {
     for (mut background, mut model_holder) in model_holders.iter_mut() {
         if background.has_been_drawn == true {
             return;
@@ -55,7 +88,7 @@ fn change_tilemap(mut model_holders: Query<(&mut Background, &mut ModelHolder)>)
                 *spot = false;
             }
 
-            let mut rng = thread_rng();
+            let mut rng = tilemap_rng(&options);
 
             for _ in 1..100 {
                 let pos: usize = rng.gen_range(0..CHUNK_COUNT);
@@ -66,16 +99,34 @@ fn change_tilemap(mut model_holders: Query<(&mut Background, &mut ModelHolder)>)
     }
 }
 
-fn spawn_tilemap(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let mut tiled =
-        crate::world::model_type::ModelHolder::new_tiled(asset_server.load("models/block.vox"));
+/// Starts the world with the model given with --load, or the tilemap unless --no-tilemap was given.
+fn spawn_world(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    options: Res<StartupOptions>,
+    settings: Res<Settings>,
+) {
+    if let Some(model) = &options.load {
+        let model = ModelHolder::new_static(asset_server.load(&*format!("models/{}.vox", model)));
+
+        commands.spawn().insert(Background::default()).insert(model);
+        return;
+    }
+
+    if options.no_tilemap {
+        return;
+    }
+
+    let mut tiled = crate::world::model_type::ModelHolder::new_tiled(
+        asset_server.load(settings.paths.tilemap_model.as_str()),
+    );
 
     if let ModelHolder::Tiled {
         ref mut filled_spots,
         ..
     } = tiled
     {
-        let mut rng = thread_rng();
+        let mut rng = tilemap_rng(&options);
 
         for _ in 1..100 {
             let pos: usize = rng.gen_range(0..CHUNK_COUNT);
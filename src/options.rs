@@ -0,0 +1,147 @@
+use std::str::FromStr;
+
+/// Options given on the command line when the app is started. This is inserted as a resource
+/// before any of the plugins are added, so plugins can read it while they are being built.
+#[derive(Clone)]
+pub struct StartupOptions {
+    /// Render the golden image scenes and compare them, instead of starting the app.
+    pub golden: bool,
+    /// With golden, overwrite the golden images instead of comparing against them.
+    pub bless: bool,
+    /// Render without a window, write the image to out, and exit.
+    pub headless: bool,
+    /// A model to start with instead of the tilemap.
+    pub load: Option<String>,
+    /// Fills in the tilemap the same way every time.
+    pub tilemap_seed: Option<u64>,
+    /// Start with an empty world.
+    pub no_tilemap: bool,
+    /// The render resolution to start with, instead of DEFAULT_RENDER_RESOLUTION.
+    pub resolution: Option<(u32, u32)>,
+    /// How many rays the voxel shader sends from each pixel.
+    pub samples: Option<u32>,
+    /// Start in borderless fullscreen, whatever settings.toml says.
+    pub fullscreen: bool,
+    /// x, y, z and yaw.
+    pub camera: Option<[f32; 4]>,
+    pub out: String,
+    /// With headless, how many frames to blend together. With benchmark, how many frames to
+    /// measure, instead of the length of the camera path.
+    pub frames: Option<u32>,
+    /// A scene to load and a camera path to play, measuring how long each frame takes.
+    pub benchmark: Option<(String, String)>,
+    /// Where the benchmark report is written. It is csv if this ends in .csv, and json otherwise.
+    pub report: String,
+    /// Scripts of console commands to run once the app has started, after autoexec.cfg.
+    pub exec: Vec<String>,
+    /// A port on localhost to listen for commands on, see control_server.
+    pub control: Option<u16>,
+}
+
+impl Default for StartupOptions {
+    fn default() -> Self {
+        StartupOptions {
+            golden: false,
+            bless: false,
+            headless: false,
+            load: None,
+            tilemap_seed: None,
+            no_tilemap: false,
+            resolution: None,
+            samples: None,
+            fullscreen: false,
+            camera: None,
+            out: "screenshot.png".into(),
+            frames: None,
+            benchmark: None,
+            report: "benchmark.json".into(),
+            exec: Vec::new(),
+            control: None,
+        }
+    }
+}
+
+pub const USAGE: &str = "usage: wgpu_test [--golden [--bless]] \
+    [--load <model> | --tilemap <seed> | --no-tilemap] [--resolution <width>x<height>] \
+    [--samples <count>] [--fullscreen] \
+    [--headless [--camera x,y,z,yaw] [--frames <count>] [--out <file.png>]] \
+    [--benchmark <model> <camera path> [--frames <count>] [--report <file.json|file.csv>]] \
+    [--exec <script.cfg>]... [--control <port>]";
+
+impl StartupOptions {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = StartupOptions::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+
+            match arg.as_str() {
+                "--golden" => options.golden = true,
+                "--bless" => options.bless = true,
+                "--headless" => options.headless = true,
+                "--load" => options.load = Some(value()?),
+                "--tilemap" => options.tilemap_seed = Some(parse_value(&value()?, "a seed")?),
+                "--no-tilemap" => options.no_tilemap = true,
+                "--resolution" => options.resolution = Some(parse_resolution(&value()?)?),
+                "--samples" => {
+                    options.samples = Some(parse_value(&value()?, "a number of samples")?)
+                }
+                "--fullscreen" => options.fullscreen = true,
+                "--camera" => options.camera = Some(parse_floats(&value()?)?),
+                "--out" => options.out = value()?,
+                "--frames" => options.frames = Some(parse_value(&value()?, "a number of frames")?),
+                "--benchmark" => options.benchmark = Some((value()?, value()?)),
+                "--report" => options.report = value()?,
+                "--exec" => options.exec.push(value()?),
+                "--control" => options.control = Some(parse_value(&value()?, "a port number")?),
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+
+        if options.load.is_some() && (options.no_tilemap || options.tilemap_seed.is_some()) {
+            return Err("--load can't be used with --tilemap or --no-tilemap".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_value<T: FromStr>(text: &str, what: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("expected {}, got {}", what, text))
+}
+
+/// Parses a size like 1280x720.
+fn parse_resolution(text: &str) -> Result<(u32, u32), String> {
+    let (width, height) = text
+        .split_once('x')
+        .ok_or_else(|| format!("expected a resolution like 1280x720, got {}", text))?;
+
+    Ok((
+        parse_value(width, "a width")?,
+        parse_value(height, "a height")?,
+    ))
+}
+
+/// Parses a comma separated list like 1.0,2,-3.5
+fn parse_floats<const N: usize>(text: &str) -> Result<[f32; N], String> {
+    let mut output = [0.0; N];
+    let mut parts = text.split(',');
+
+    for value in output.iter_mut() {
+        let part = parts
+            .next()
+            .ok_or_else(|| format!("expected {} comma separated numbers, got {}", N, text))?;
+
+        *value = part
+            .trim()
+            .parse()
+            .map_err(|_| format!("expected a number, got {}", part))?;
+    }
+
+    if parts.next().is_some() {
+        return Err(format!("expected {} comma separated numbers, got {}", N, text));
+    }
+
+    Ok(output)
+}
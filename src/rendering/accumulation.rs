@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgSpec, CommandSpec, ON_OFF};
+use crate::debug::Command;
+use crate::GPUData;
+
+/// After this many frames, new frames stop getting less weight than the last. The temp image only
+/// has 8 bits per channel, so anything smaller than this would be rounded away.
+const MAX_ACCUMULATED_FRAMES: u32 = 31;
+
+/// While nothing that changes the traced picture is changing, each frame is blended into the temp
+/// image with a weight of 1 / (accumulated_frames + 1), which averages all of them together.
+pub struct Accumulation {
+    pub enabled: bool,
+    /// Set when something outside of GPUData changes the picture, like the world being edited.
+    pub needs_reset: bool,
+    last_trace_inputs: Option<GPUData>,
+}
+
+impl Default for Accumulation {
+    fn default() -> Self {
+        Accumulation {
+            enabled: true,
+            needs_reset: true,
+            last_trace_inputs: None,
+        }
+    }
+}
+
+impl Accumulation {
+    pub fn reset(&mut self) {
+        self.needs_reset = true;
+    }
+}
+
+/// Everything in GPUData that the voxel shader reads, except for the frame seed, the denoiser
+/// settings and the frame time graph, which are applied after accumulation. If any of it changes,
+/// the old frames no longer match the new ones.
+fn trace_inputs(gpu_data: &GPUData) -> GPUData {
+    let mut inputs = *gpu_data;
+
+    inputs.time = 0;
+    inputs.accumulated_frames = 0;
+    inputs.denoise_iterations = 0;
+    inputs.denoise_step = 0;
+    inputs.denoise_strength = 0.0;
+    inputs.frame_time_graph = [0; 16];
+    inputs.show_hud = 0;
+
+    inputs
+}
+
+/// Runs in the render stage, after everything else has had a chance to change GPUData.
+pub fn update_accumulation(
+    mut gpu_data: ResMut<GPUData>,
+    mut accumulation: ResMut<Accumulation>,
+) {
+    // every frame gets a different seed, so that the noise is different each time.
+    gpu_data.time = gpu_data.time.wrapping_add(1);
+
+    let trace_inputs = trace_inputs(&gpu_data);
+
+    let inputs_changed = match &accumulation.last_trace_inputs {
+        Some(last) => bytemuck::bytes_of(last) != bytemuck::bytes_of(&trace_inputs),
+        None => true,
+    };
+
+    if !accumulation.enabled || accumulation.needs_reset || inputs_changed {
+        gpu_data.accumulated_frames = 0;
+    } else {
+        gpu_data.accumulated_frames = (gpu_data.accumulated_frames + 1).min(MAX_ACCUMULATED_FRAMES);
+    }
+
+    accumulation.needs_reset = false;
+    accumulation.last_trace_inputs = Some(trace_inputs);
+}
+
+pub const COMMANDS: &[CommandSpec] = &[CommandSpec {
+    name: "accumulate",
+    description: "turns blending frames together while the camera is still on or off.",
+    args: &[ArgSpec::new("enabled", ON_OFF)],
+}];
+
+pub fn accumulation_commands(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut accumulation: ResMut<Accumulation>,
+) {
+    for cmd in commands.iter().filter(|cmd| cmd.is("accumulate")) {
+        match cmd.get_arg(0) {
+            "on" => accumulation.enabled = true,
+            "off" => accumulation.enabled = false,
+            other => console.send(cmd.error(format!("expected on or off, got {}.", other))),
+        }
+
+        accumulation.reset();
+    }
+}
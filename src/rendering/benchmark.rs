@@ -0,0 +1,317 @@
+// The --benchmark mode. A scene is loaded, a camera path is played from start to end with a fixed
+// step each frame, and how long each frame took is written to a report, so that two builds can be
+// compared on exactly the same frames.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use bevy::app::Events;
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::debug::Command;
+use crate::rendering::camera::path::{CameraPath, DEFAULT_SPEED};
+use crate::rendering::headless::{is_world_loaded, LOAD_TIMEOUT};
+use crate::world::voxel_world::VoxelWorld;
+use crate::GPUData;
+
+enum BenchmarkStage {
+    /// Waiting for the world the app starts with, so that it isn't drawn over the scene.
+    WaitingForWorld,
+    WaitingForScene,
+    Running { frames_left: u32 },
+    Finished,
+}
+
+/// How long the parts of one frame took.
+#[derive(Clone, Copy)]
+pub struct FrameTiming {
+    /// From the start of the last frame to the start of this one.
+    pub frame: Duration,
+    /// Running every system once.
+    pub update: Duration,
+    /// Waiting for the last frame, recording and submitting the command buffer, and presenting.
+    pub render: Duration,
+}
+
+pub struct Benchmark {
+    scene: String,
+    camera_path: PathBuf,
+    frames: Option<u32>,
+    report: PathBuf,
+    stage: BenchmarkStage,
+    started_loading: Instant,
+    last_frame_start: Option<Instant>,
+    timings: Vec<FrameTiming>,
+}
+
+impl Benchmark {
+    /// camera_path can either be a file, or the name of a path saved with `path save`.
+    pub fn new(scene: &str, camera_path: &str, frames: Option<u32>, report: &str) -> Self {
+        let camera_path = if Path::new(camera_path).exists() {
+            PathBuf::from(camera_path)
+        } else {
+            CameraPath::file_for(camera_path)
+        };
+
+        Benchmark {
+            scene: scene.to_string(),
+            camera_path,
+            frames,
+            report: PathBuf::from(report),
+            stage: BenchmarkStage::WaitingForWorld,
+            started_loading: Instant::now(),
+            last_frame_start: None,
+            timings: Vec::new(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.stage, BenchmarkStage::Running { .. })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.stage, BenchmarkStage::Finished)
+    }
+
+    /// Called by the event loop after each frame has been drawn.
+    pub fn record(&mut self, update: Duration, render: Duration) {
+        let frames_left = match &mut self.stage {
+            BenchmarkStage::Running { frames_left } => frames_left,
+            _ => return,
+        };
+
+        let now = Instant::now();
+        let frame = self
+            .last_frame_start
+            .map_or(update + render, |last| now - last);
+
+        self.last_frame_start = Some(now);
+        self.timings.push(FrameTiming {
+            frame,
+            update,
+            render,
+        });
+
+        *frames_left -= 1;
+
+        if *frames_left == 0 {
+            self.stage = BenchmarkStage::Finished;
+        }
+    }
+}
+
+/// Called by the event loop before each update. Loads the scene and starts the camera path once
+/// the world is ready. Fails if the scene or the path can't be loaded.
+pub fn update_benchmark(world: &mut World) -> anyhow::Result<()> {
+    let benchmark = world.get_resource::<Benchmark>().unwrap();
+
+    if benchmark.is_running() || benchmark.is_finished() {
+        return Ok(());
+    }
+
+    let loaded = is_world_loaded(world)?;
+
+    let mut benchmark = world.get_resource_mut::<Benchmark>().unwrap();
+
+    if benchmark.started_loading.elapsed() > LOAD_TIMEOUT {
+        anyhow::bail!("timed out waiting for {} to load", benchmark.scene);
+    }
+
+    if !loaded {
+        return Ok(());
+    }
+
+    if matches!(benchmark.stage, BenchmarkStage::WaitingForWorld) {
+        benchmark.stage = BenchmarkStage::WaitingForScene;
+
+        let scene = benchmark.scene.clone();
+
+        world
+            .get_resource_mut::<Events<Command>>()
+            .unwrap()
+            .send(Command {
+                function: "load".into(),
+                arguments: vec![scene],
+                control_request: None,
+            });
+    } else if matches!(benchmark.stage, BenchmarkStage::WaitingForScene) {
+        let mut camera_path = CameraPath::load(&benchmark.camera_path)?;
+
+        if camera_path.keys.is_empty() {
+            anyhow::bail!("{} has no keys", benchmark.camera_path.display());
+        }
+
+        // the path loops, so asking for more frames than it has just goes around again.
+        let frames_left = benchmark
+            .frames
+            .unwrap_or_else(|| camera_path.frame_count(DEFAULT_SPEED))
+            .max(1);
+
+        benchmark.stage = BenchmarkStage::Running { frames_left };
+        camera_path.play(DEFAULT_SPEED, true);
+
+        world.insert_resource(camera_path);
+    }
+
+    Ok(())
+}
+
+/// Everything in the report besides the timings of each frame.
+#[derive(Serialize)]
+struct Summary {
+    resolution: (u32, u32),
+    #[serde(rename = "samples_per_pixel")]
+    samples: u32,
+    filled_chunks: usize,
+    frame_count: usize,
+    frame_ms: Stats,
+    update_ms: Stats,
+    render_ms: Stats,
+}
+
+#[derive(Serialize)]
+struct Stats {
+    min: f64,
+    avg: f64,
+    p95: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl Stats {
+    fn of(durations: impl Iterator<Item = Duration>) -> Self {
+        let mut ms: Vec<f64> = durations.map(|duration| duration.as_secs_f64() * 1000.0).collect();
+        ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let percentile = |p: f64| {
+            let index = ((ms.len() as f64 * p).ceil() as usize).clamp(1, ms.len()) - 1;
+            ms[index]
+        };
+
+        Stats {
+            min: ms[0],
+            avg: ms.iter().sum::<f64>() / ms.len() as f64,
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: ms[ms.len() - 1],
+        }
+    }
+}
+
+/// Writes the report, and prints the frame time stats. Returns where it was written.
+pub fn write_report(world: &World, resolution: (u32, u32)) -> anyhow::Result<PathBuf> {
+    let benchmark = world.get_resource::<Benchmark>().unwrap();
+    let voxel_world = world.get_resource::<VoxelWorld>().unwrap();
+
+    let timings = &benchmark.timings;
+
+    let summary = Summary {
+        resolution,
+        samples: world.get_resource::<GPUData>().unwrap().samples,
+        filled_chunks: voxel_world.filled_chunks.iter().filter(|filled| **filled).count(),
+        frame_count: timings.len(),
+        frame_ms: Stats::of(timings.iter().map(|timing| timing.frame)),
+        update_ms: Stats::of(timings.iter().map(|timing| timing.update)),
+        render_ms: Stats::of(timings.iter().map(|timing| timing.render)),
+    };
+
+    println!(
+        "{} frames, frame time min {:.2}ms avg {:.2}ms p95 {:.2}ms p99 {:.2}ms",
+        timings.len(),
+        summary.frame_ms.min,
+        summary.frame_ms.avg,
+        summary.frame_ms.p95,
+        summary.frame_ms.p99
+    );
+
+    let is_csv = benchmark.report.extension().map_or(false, |ext| ext == "csv");
+
+    let text = if is_csv {
+        report_csv(benchmark, &summary)
+    } else {
+        report_json(benchmark, &summary)?
+    };
+
+    std::fs::write(&benchmark.report, text)?;
+
+    Ok(benchmark.report.clone())
+}
+
+/// The json report, which is the summary with the timings of each frame after it.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    scene: &'a str,
+    camera_path: String,
+    #[serde(flatten)]
+    summary: &'a Summary,
+    frames: Vec<JsonFrame>,
+}
+
+#[derive(Serialize)]
+struct JsonFrame {
+    frame_ms: f64,
+    update_ms: f64,
+    render_ms: f64,
+}
+
+fn report_json(benchmark: &Benchmark, summary: &Summary) -> serde_json::Result<String> {
+    let report = JsonReport {
+        scene: &benchmark.scene,
+        camera_path: benchmark.camera_path.display().to_string(),
+        summary,
+        frames: benchmark
+            .timings
+            .iter()
+            .map(|timing| JsonFrame {
+                frame_ms: timing.frame.as_secs_f64() * 1000.0,
+                update_ms: timing.update.as_secs_f64() * 1000.0,
+                render_ms: timing.render.as_secs_f64() * 1000.0,
+            })
+            .collect(),
+    };
+
+    serde_json::to_string_pretty(&report)
+}
+
+/// One row per frame. The summary is written above the rows as comments, so that the file can
+/// still be read by anything that skips lines starting with #.
+fn report_csv(benchmark: &Benchmark, summary: &Summary) -> String {
+    let mut csv = String::new();
+
+    writeln!(csv, "# scene: {}", benchmark.scene).unwrap();
+    writeln!(csv, "# camera_path: {}", benchmark.camera_path.display()).unwrap();
+    writeln!(csv, "# resolution: {}x{}", summary.resolution.0, summary.resolution.1).unwrap();
+    writeln!(csv, "# samples_per_pixel: {}", summary.samples).unwrap();
+    writeln!(csv, "# filled_chunks: {}", summary.filled_chunks).unwrap();
+
+    for (name, stats) in [
+        ("frame_ms", &summary.frame_ms),
+        ("update_ms", &summary.update_ms),
+        ("render_ms", &summary.render_ms),
+    ] {
+        writeln!(
+            csv,
+            "# {}: min {:.3} avg {:.3} p95 {:.3} p99 {:.3} max {:.3}",
+            name, stats.min, stats.avg, stats.p95, stats.p99, stats.max
+        )
+        .unwrap();
+    }
+
+    writeln!(csv, "frame,frame_ms,update_ms,render_ms").unwrap();
+
+    for (index, timing) in benchmark.timings.iter().enumerate() {
+        writeln!(
+            csv,
+            "{},{:.3},{:.3},{:.3}",
+            index,
+            timing.frame.as_secs_f64() * 1000.0,
+            timing.update.as_secs_f64() * 1000.0,
+            timing.render.as_secs_f64() * 1000.0
+        )
+        .unwrap();
+    }
+
+    csv
+}
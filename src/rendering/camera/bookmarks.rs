@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::rendering::camera::{Camera, CameraPose};
+
+/// Where bookmarks are kept between runs, relative to the working directory.
+pub const BOOKMARKS_PATH: &str = "camera_bookmarks.txt";
+
+/// Named camera poses, for getting back to the same view when comparing changes to the renderer.
+/// Each line of the file is a name followed by the pose, see CameraPose::to_line.
+#[derive(Default)]
+pub struct Bookmarks(pub BTreeMap<String, CameraPose>);
+
+impl Bookmarks {
+    /// Reads the bookmarks from a file. A missing file means there are no bookmarks yet, and lines
+    /// that can't be read are skipped with a warning.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let mut bookmarks = Bookmarks::default();
+
+        let text = match std::fs::read_to_string(path.as_ref()) {
+            Ok(text) => text,
+            Err(_) => return bookmarks,
+        };
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, pose) = line.split_once(' ').unwrap_or((line, ""));
+
+            match CameraPose::from_line(pose) {
+                Ok(pose) => {
+                    bookmarks.0.insert(name.to_string(), pose);
+                }
+                Err(err) => println!("{}:{}: {}", path.as_ref().display(), line_number + 1, err),
+            }
+        }
+
+        bookmarks
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let text: String = self
+            .0
+            .iter()
+            .map(|(name, pose)| format!("{} {}\n", name, pose.to_line()))
+            .collect();
+
+        std::fs::write(path, text)
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[CommandSpec {
+    name: "bookmark",
+    description: "saves, goes to, deletes or lists named camera positions.",
+    args: &[
+        ArgSpec::new("action", ArgKind::Enum(&["save", "go", "delete", "list"])),
+        ArgSpec::new("name", ArgKind::Text).optional(),
+    ],
+}];
+
+/// `bookmark save <name>` remembers where the camera is, `bookmark go <name>` moves it back there,
+/// `bookmark delete <name>` forgets one and `bookmark list` prints their names. Changes are saved
+/// straight away.
+pub fn bookmark_commands(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut bookmarks: ResMut<Bookmarks>,
+    mut camera: ResMut<Camera>,
+) {
+    for cmd in commands.iter().filter(|cmd| cmd.is("bookmark")) {
+        let name = cmd.arguments.get(1);
+
+        let changed = match (cmd.get_arg(0), name) {
+            // the file has the name and the pose on one line, split at the first space.
+            ("save", Some(name)) if name.is_empty() || name.contains(char::is_whitespace) => {
+                let err = format!(
+                    "bookmark names can't be empty or have spaces, got \"{}\".",
+                    name
+                );
+
+                console.send(cmd.error(err));
+                false
+            }
+            ("save", Some(name)) => {
+                bookmarks.0.insert(name.clone(), camera.pose());
+                true
+            }
+            ("go", Some(name)) => {
+                match bookmarks.0.get(name) {
+                    Some(pose) => camera.set_pose(*pose),
+                    None => console.send(cmd.error(format!("no bookmark called {}.", name))),
+                }
+                false
+            }
+            ("delete", Some(name)) => {
+                let removed = bookmarks.0.remove(name).is_some();
+
+                if !removed {
+                    console.send(cmd.error(format!("no bookmark called {}.", name)));
+                }
+                removed
+            }
+            ("list", _) => {
+                let names: Vec<&str> = bookmarks.0.keys().map(String::as_str).collect();
+
+                console.send(cmd.info(names.join("\n")));
+                false
+            }
+            _ => {
+                console.send(cmd.error("expected save, go or delete and a name, or list."));
+                false
+            }
+        };
+
+        if !changed {
+            continue;
+        }
+
+        if let Err(err) = bookmarks.save(BOOKMARKS_PATH) {
+            console.send(cmd.error(format!("could not save bookmarks: {}", err)));
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::GPUData;
+
+pub mod bookmarks;
+pub mod path;
+
+const DEFAULT_FOV: f32 = std::f32::consts::FRAC_PI_2;
+const MIN_FOV_DEGREES: f32 = 10.0;
+const MAX_FOV_DEGREES: f32 = 170.0;
+
+const DEFAULT_ORTHO_SIZE: f32 = 32.0;
+const MIN_ORTHO_SIZE: f32 = 1.0;
+const MAX_ORTHO_SIZE: f32 = 1024.0;
+
+/// The classic isometric angle: turned 45 degrees, and looking down far enough that all three axes
+/// are the same length on screen.
+const ISOMETRIC_YAW: f32 = std::f32::consts::FRAC_PI_4;
+const ISOMETRIC_PITCH: f32 = -0.615_479_7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    /// Every ray points the same way, starting from a spot on a plane through the camera.
+    Orthographic,
+    /// Orthographic, from the isometric angle. Choosing it turns the camera to that angle.
+    Isometric,
+}
+
+impl Projection {
+    pub fn is_orthographic(self) -> bool {
+        self != Projection::Perspective
+    }
+}
+
+/// Where the picture is seen from. This is the only thing that should move the view, it is copied
+/// into GPUData once a frame by write_camera_data.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub position: Vec3,
+    /// Turns +z into the direction the camera is looking, +x into the right of the screen and +y
+    /// into the top of it.
+    pub orientation: Quat,
+    /// The vertical field of view, in radians.
+    pub fov: f32,
+    pub projection: Projection,
+    /// Half of how tall the view is in voxels, when the projection is orthographic.
+    pub ortho_size: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+            fov: DEFAULT_FOV,
+            projection: Projection::Perspective,
+            ortho_size: DEFAULT_ORTHO_SIZE,
+        }
+    }
+}
+
+impl Camera {
+    /// A camera that has been turned right by yaw, then tilted up by pitch, both in radians.
+    pub fn looking(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        Camera {
+            position,
+            orientation: Quat::from_rotation_y(-yaw) * Quat::from_rotation_x(-pitch),
+            ..Camera::default()
+        }
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.orientation * Vec3::X
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.orientation * Vec3::Y
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * Vec3::Z
+    }
+
+    /// Turns around the world's up axis, so that the horizon stays level.
+    pub fn turn(&mut self, angle: f32) {
+        self.orientation = (Quat::from_rotation_y(-angle) * self.orientation).normalize();
+    }
+
+    /// Tilts up or down around the camera's own right axis.
+    pub fn tilt(&mut self, angle: f32) {
+        self.orientation = (self.orientation * Quat::from_rotation_x(-angle)).normalize();
+    }
+
+    /// Rolls around the direction the camera is looking.
+    pub fn roll(&mut self, angle: f32) {
+        self.orientation = (self.orientation * Quat::from_rotation_z(angle)).normalize();
+    }
+
+    /// Takes out any roll, so that the horizon is level, while still looking the same way.
+    pub fn remove_roll(&mut self) {
+        let forward = self.forward();
+        let yaw = (-forward.x).atan2(forward.z);
+        let pitch = forward.y.clamp(-1.0, 1.0).asin();
+
+        self.orientation = Camera::looking(self.position, yaw, pitch).orientation;
+    }
+
+    /// How far the screen is in front of the camera, for a screen that is 2 units tall.
+    pub fn focal_length(&self) -> f32 {
+        1.0 / (self.fov / 2.0).tan()
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            position: self.position,
+            orientation: self.orientation,
+            fov: self.fov,
+        }
+    }
+
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        self.position = pose.position;
+        self.orientation = pose.orientation;
+        self.fov = pose.fov;
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        if projection == Projection::Isometric {
+            self.orientation = Camera::looking(self.position, ISOMETRIC_YAW, ISOMETRIC_PITCH)
+                .orientation;
+        }
+
+        self.projection = projection;
+    }
+
+    /// Zooms in when amount is above 1, and out when it is below. Perspective cameras zoom by
+    /// narrowing their field of view, and orthographic ones by showing less of the world.
+    pub fn zoom(&mut self, amount: f32) {
+        if self.projection.is_orthographic() {
+            self.ortho_size = (self.ortho_size / amount).clamp(MIN_ORTHO_SIZE, MAX_ORTHO_SIZE);
+        } else {
+            let degrees = (self.fov.to_degrees() / amount).clamp(MIN_FOV_DEGREES, MAX_FOV_DEGREES);
+
+            self.fov = degrees.to_radians();
+        }
+    }
+
+    /// Writes the camera basis that voxel_render.frag builds its rays from. For orthographic
+    /// projections, camera_right.w holds ortho_size, and is 0 otherwise.
+    pub fn write_to(&self, gpu_data: &mut GPUData) {
+        gpu_data.pos = self.position.extend(0.0).to_array();
+        gpu_data.camera_up = self.up().extend(0.0).to_array();
+
+        if self.projection.is_orthographic() {
+            gpu_data.camera_right = self.right().extend(self.ortho_size).to_array();
+            gpu_data.camera_forward = self.forward().extend(0.0).to_array();
+        } else {
+            gpu_data.camera_right = self.right().extend(0.0).to_array();
+            gpu_data.camera_forward =
+                (self.forward() * self.focal_length()).extend(0.0).to_array();
+        }
+    }
+}
+
+/// The parts of a Camera that bookmarks and paths remember.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub orientation: Quat,
+    pub fov: f32,
+}
+
+impl CameraPose {
+    /// Writes the pose as `x y z qx qy qz qw fov`, with fov in degrees.
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {} {} {} {} {}",
+            self.position.x,
+            self.position.y,
+            self.position.z,
+            self.orientation.x,
+            self.orientation.y,
+            self.orientation.z,
+            self.orientation.w,
+            self.fov.to_degrees()
+        )
+    }
+
+    /// Reads a pose written by to_line.
+    pub fn from_line(line: &str) -> Result<Self, String> {
+        let numbers = line
+            .split_whitespace()
+            .map(|part| {
+                part.parse::<f32>()
+                    .map_err(|_| format!("expected a number, got {}", part))
+            })
+            .collect::<Result<Vec<f32>, String>>()?;
+
+        match numbers[..] {
+            [x, y, z, qx, qy, qz, qw, fov] => Ok(CameraPose {
+                position: Vec3::new(x, y, z),
+                orientation: Quat::from_xyzw(qx, qy, qz, qw).normalize(),
+                fov: fov.to_radians(),
+            }),
+            _ => Err(format!("expected 8 numbers, got {}", numbers.len())),
+        }
+    }
+}
+
+/// The same ray setup as voxel_render.frag, for anything on the cpu that needs to know what is
+/// under a point on the screen. screen_pos goes from -1 to 1 on both axes, with y pointing down.
+/// Returns where the ray starts and the direction it goes in.
+pub fn screen_ray(gpu_data: &GPUData, screen_pos: Vec2) -> (Vec3, Vec3) {
+    let camera_pos = Vec3::from_slice(&gpu_data.pos);
+    let right = Vec3::from_slice(&gpu_data.camera_right);
+    let up = Vec3::from_slice(&gpu_data.camera_up);
+    let forward = Vec3::from_slice(&gpu_data.camera_forward);
+    let ortho_size = gpu_data.camera_right[3];
+
+    let screen_offset = right * screen_pos.x * gpu_data.aspect_ratio - up * screen_pos.y;
+
+    if ortho_size > 0.0 {
+        (camera_pos + screen_offset * ortho_size, forward.normalize())
+    } else {
+        (camera_pos, (forward + screen_offset).normalize())
+    }
+}
+
+/// Runs in the render stage, so the picture always matches where the camera ended up this frame.
+pub fn write_camera_data(camera: Res<Camera>, mut gpu_data: ResMut<GPUData>) {
+    camera.write_to(&mut gpu_data);
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "fov",
+        description: "sets the vertical field of view.",
+        args: &[ArgSpec::new("degrees", ArgKind::Float)],
+    },
+    CommandSpec {
+        name: "projection",
+        description: "switches between perspective, orthographic and isometric views.",
+        args: &[ArgSpec::new("projection", ArgKind::Enum(&["persp", "ortho", "iso"]))],
+    },
+    CommandSpec {
+        name: "zoom",
+        description: "sets how many voxels tall an orthographic view is.",
+        args: &[ArgSpec::new("voxels", ArgKind::Float)],
+    },
+    CommandSpec {
+        name: "camera_pose",
+        description: "prints where the camera is as x y z qx qy qz qw fov.",
+        args: &[],
+    },
+];
+
+fn parse_projection(text: &str) -> Result<Projection, String> {
+    match text {
+        "persp" => Ok(Projection::Perspective),
+        "ortho" => Ok(Projection::Orthographic),
+        "iso" => Ok(Projection::Isometric),
+        other => Err(format!("expected persp, ortho or iso, got {}.", other)),
+    }
+}
+
+/// `fov <degrees>` sets the vertical field of view, `projection persp|ortho|iso` switches between
+/// projections, and `zoom <voxels>` sets how many voxels tall an orthographic view is.
+/// `camera_pose` prints the camera's pose, in the same format bookmarks are saved in.
+pub fn camera_commands(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut camera: ResMut<Camera>,
+) {
+    for cmd in commands.iter() {
+        let result = match cmd.function.as_str() {
+            "fov" => cmd.parse_arg::<f32>(0).map(|degrees| {
+                camera.fov = degrees.clamp(MIN_FOV_DEGREES, MAX_FOV_DEGREES).to_radians()
+            }),
+            "projection" => {
+                parse_projection(cmd.get_arg(0)).map(|projection| camera.set_projection(projection))
+            }
+            "zoom" => cmd.parse_arg::<f32>(0).map(|size| {
+                camera.ortho_size = (size / 2.0).clamp(MIN_ORTHO_SIZE, MAX_ORTHO_SIZE)
+            }),
+            "camera_pose" => {
+                console.send(cmd.info(camera.pose().to_line()));
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            console.send(cmd.error(err));
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::rendering::camera::bookmarks::Bookmarks;
+use crate::rendering::camera::{Camera, CameraPose};
+
+/// Where paths are saved, relative to the working directory.
+pub const CAMERA_PATH_DIR: &str = "camera_paths";
+
+/// Playback moves forward by this much every frame, no matter how long the frame took, so that a
+/// path always shows the same frames in the same order.
+pub const PATH_FRAME_TIME: f32 = 1.0 / 60.0;
+
+/// Keys per second, when playing a path without saying how fast.
+pub const DEFAULT_SPEED: f32 = 0.5;
+
+/// A list of camera poses that the camera can be moved smoothly through. Positions and fov follow a
+/// Catmull-Rom spline through the keys, and the orientation turns between them.
+#[derive(Default)]
+pub struct CameraPath {
+    pub keys: Vec<CameraPose>,
+    playback: Option<Playback>,
+}
+
+struct Playback {
+    /// How far along the path the camera is, in keys. 1.5 is halfway between the second and third.
+    progress: f32,
+    /// Keys per second.
+    speed: f32,
+    looping: bool,
+}
+
+impl CameraPath {
+    /// Reads a path saved with save, one pose per line.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path.as_ref())?;
+
+        let keys = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(CameraPose::from_line)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| anyhow::anyhow!("{}: {}", path.as_ref().display(), err))?;
+
+        Ok(CameraPath {
+            keys,
+            playback: None,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let text: String = self
+            .keys
+            .iter()
+            .map(|pose| pose.to_line() + "\n")
+            .collect();
+
+        std::fs::write(path, text)
+    }
+
+    /// The file a path with this name is saved to.
+    pub fn file_for(name: &str) -> PathBuf {
+        PathBuf::from(CAMERA_PATH_DIR).join(format!("{}.path", name))
+    }
+
+    pub fn play(&mut self, speed: f32, looping: bool) {
+        self.playback = Some(Playback {
+            progress: 0.0,
+            speed,
+            looping,
+        });
+    }
+
+    pub fn stop(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// How long the path takes to play from start to end at the given speed, in frames.
+    pub fn frame_count(&self, speed: f32) -> u32 {
+        let length = self.keys.len().saturating_sub(1) as f32;
+
+        (length / (speed * PATH_FRAME_TIME)).ceil() as u32 + 1
+    }
+
+    /// The pose at a point along the path, in keys. There must be at least one key.
+    pub fn sample(&self, progress: f32) -> CameraPose {
+        let last = self.keys.len() - 1;
+        let progress = progress.clamp(0.0, last as f32);
+
+        let index = (progress.floor() as usize).min(last.saturating_sub(1));
+        let t = progress - index as f32;
+
+        // the first and last keys are repeated to give the spline something to bend toward.
+        let key = |offset: isize| {
+            let clamped = (index as isize + offset).clamp(0, last as isize);
+            self.keys[clamped as usize]
+        };
+
+        let (before, from, to, after) = (key(-1), key(0), key(1), key(2));
+
+        CameraPose {
+            position: catmull_rom(before.position, from.position, to.position, after.position, t),
+            orientation: from.orientation.slerp(to.orientation, smoothstep(t)),
+            fov: catmull_rom(
+                Vec3::splat(before.fov),
+                Vec3::splat(from.fov),
+                Vec3::splat(to.fov),
+                Vec3::splat(after.fov),
+                t,
+            )
+            .x,
+        }
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * ((2.0 * p1)
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Moves the camera along the path while it is playing. Runs in the render stage, after anything
+/// else that moves the camera, so that playback can't be pushed off course.
+pub fn play_camera_path(mut path: ResMut<CameraPath>, mut camera: ResMut<Camera>) {
+    if path.keys.is_empty() {
+        path.stop();
+        return;
+    }
+
+    let last = (path.keys.len() - 1) as f32;
+
+    let playback = match &mut path.playback {
+        Some(playback) => playback,
+        None => return,
+    };
+
+    // the last step is cut short so that it lands right on the last key.
+    let progress = playback.progress.min(last);
+
+    if progress < last {
+        playback.progress += playback.speed * PATH_FRAME_TIME;
+    } else if playback.looping {
+        playback.progress = 0.0;
+    } else {
+        path.playback = None;
+    }
+
+    camera.set_pose(path.sample(progress));
+}
+
+pub const COMMANDS: &[CommandSpec] = &[CommandSpec {
+    name: "path",
+    description: "builds, plays, saves and loads camera paths.",
+    args: &[
+        ArgSpec::new(
+            "action",
+            ArgKind::Enum(&["add", "clear", "play", "stop", "save", "load"]),
+        ),
+        ArgSpec::new("bookmark|speed|name", ArgKind::Text).optional(),
+        ArgSpec::new("loop", ArgKind::Enum(&["loop"])).optional(),
+    ],
+}];
+
+/// `path add [bookmark]` adds the camera, or a bookmark, as the next key. `path clear` removes all
+/// the keys. `path play [keys per second] [loop]` and `path stop` control playback. `path save
+/// <name>` and `path load <name>` keep paths in the camera_paths directory.
+pub fn camera_path_commands(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut path: ResMut<CameraPath>,
+    camera: Res<Camera>,
+    bookmarks: Res<Bookmarks>,
+) {
+    for cmd in commands.iter().filter(|cmd| cmd.is("path")) {
+        match cmd.get_arg(0) {
+            "add" => match cmd.arguments.get(1) {
+                Some(name) => match bookmarks.0.get(name) {
+                    Some(pose) => path.keys.push(*pose),
+                    None => console.send(cmd.error(format!("no bookmark called {}.", name))),
+                },
+                None => path.keys.push(camera.pose()),
+            },
+            "clear" => {
+                path.keys.clear();
+                path.stop();
+            }
+            "play" => {
+                let speed = match cmd.arguments.get(1) {
+                    Some(_) => cmd.parse_arg(1),
+                    None => Ok(DEFAULT_SPEED),
+                };
+                let looping = cmd.get_arg(2) == "loop";
+
+                match speed {
+                    Ok(speed) if speed > 0.0 => path.play(speed, looping),
+                    Ok(_) => console.send(cmd.error("path: the speed has to be more than 0.")),
+                    Err(err) => console.send(cmd.error(err)),
+                }
+            }
+            "stop" => path.stop(),
+            "save" | "load" if cmd.get_arg(1).is_empty() => {
+                console.send(cmd.error("path: expected the name of a path."))
+            }
+            "save" => {
+                let file = CameraPath::file_for(cmd.get_arg(1));
+                let result = std::fs::create_dir_all(CAMERA_PATH_DIR)
+                    .and_then(|_| path.save(&file));
+
+                console.send(match result {
+                    Ok(()) => cmd.info(format!("saved camera path to {}", file.display())),
+                    Err(err) => cmd.error(format!("could not save camera path: {}", err)),
+                });
+            }
+            "load" => match CameraPath::load(CameraPath::file_for(cmd.get_arg(1))) {
+                Ok(loaded) => *path = loaded,
+                Err(err) => {
+                    console.send(cmd.error(format!("could not load camera path, {}.", err)))
+                }
+            },
+            other => console.send(cmd.error(format!("unknown path command {}.", other))),
+        }
+    }
+}
@@ -63,4 +63,63 @@ pub unsafe fn create_buffer_bindings<B: gfx_hal::Backend>(
     ]);
 
     (set_layout, desc_set)
-}
\ No newline at end of file
+}
+
+/// Binds each buffer as a read-only storage buffer, at the binding matching its index.
+pub unsafe fn create_storage_buffer_bindings<B: gfx_hal::Backend>(
+    device: &B::Device,
+    buffers: &[&B::Buffer],
+) -> (B::DescriptorSetLayout, B::DescriptorSet) {
+    let descriptor_type = DescriptorType::Buffer {
+        ty: BufferDescriptorType::Storage { read_only: true },
+        format: BufferDescriptorFormat::Structured {
+            dynamic_offset: false,
+        },
+    };
+
+    let bindings: Vec<_> = (0..buffers.len())
+        .map(|binding| DescriptorSetLayoutBinding {
+            binding: binding as u32,
+            ty: descriptor_type,
+            count: 1,
+            stage_flags: ShaderStageFlags::FRAGMENT,
+            immutable_samplers: false,
+        })
+        .collect();
+
+    let set_layout = device
+        .create_descriptor_set_layout(&bindings, &[])
+        .expect("Can't create descriptor set layout");
+
+    let mut desc_pool = device
+        .create_descriptor_pool(
+            1, // sets
+            &[DescriptorRangeDesc {
+                ty: descriptor_type,
+                count: buffers.len(),
+            }],
+            gfx_hal::pso::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+        )
+        .expect("Can't create descriptor pool");
+
+    let desc_set = desc_pool
+        .allocate_set(&set_layout)
+        .expect("unable to allocate set layout for description pool");
+
+    device.write_descriptor_sets(buffers.iter().enumerate().map(|(binding, buffer)| {
+        DescriptorSetWrite {
+            set: &desc_set,
+            binding: binding as u32,
+            array_offset: 0,
+            descriptors: Some(Descriptor::Buffer(
+                *buffer,
+                SubRange {
+                    offset: 0,
+                    size: None,
+                },
+            )),
+        }
+    }));
+
+    (set_layout, desc_set)
+}
@@ -84,4 +84,106 @@ pub unsafe fn create_image_bindings<B: gfx_hal::Backend>(
     ]);
 
     (set_layout, desc_set, sampler)
-}
\ No newline at end of file
+}
+
+/// Like create_image_bindings, but for several images that share one sampler. Each image is bound
+/// at the binding matching its position in image_views, and the sampler comes after all of them.
+pub unsafe fn create_multi_image_bindings<B: gfx_hal::Backend>(
+    device: &B::Device,
+    image_views: &[&B::ImageView],
+) -> (B::DescriptorSetLayout, B::DescriptorSet, B::Sampler) {
+    let sampler_binding = image_views.len() as u32;
+
+    let mut bindings: Vec<DescriptorSetLayoutBinding> = (0..sampler_binding)
+        .map(|binding| DescriptorSetLayoutBinding {
+            binding,
+            ty: DescriptorType::Image {
+                ty: ImageDescriptorType::Sampled {
+                    with_sampler: true
+                }
+            },
+            count: 1,
+            stage_flags: ShaderStageFlags::FRAGMENT,
+            immutable_samplers: false,
+        })
+        .collect();
+
+    bindings.push(DescriptorSetLayoutBinding {
+        binding: sampler_binding,
+        ty: DescriptorType::Sampler,
+        count: 1,
+        stage_flags: ShaderStageFlags::FRAGMENT,
+        immutable_samplers: false,
+    });
+
+    let set_layout = device
+        .create_descriptor_set_layout(&bindings, &[])
+        .expect("Ran out of memory creating descriptor set layout");
+
+    let mut desc_pool = device.create_descriptor_pool(
+        1, // sets
+        &[
+            DescriptorRangeDesc {
+                ty: DescriptorType::Image {
+                    ty: ImageDescriptorType::Sampled {
+                        with_sampler: true
+                    }
+                },
+                count: image_views.len(),
+            },
+            DescriptorRangeDesc {
+                ty: DescriptorType::Sampler,
+                count: 1,
+            },
+        ],
+        DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+    )
+        .expect("Can't create descriptor pool");
+
+    let desc_set = desc_pool.allocate_set(&set_layout).unwrap();
+
+    let sampler =
+        device
+            .create_sampler(&SamplerDesc::new(Filter::Nearest, WrapMode::Clamp))
+            .unwrap();
+
+    let mut writes: Vec<DescriptorSetWrite<_, _>> = image_views
+        .iter()
+        .enumerate()
+        .map(|(binding, image_view)| DescriptorSetWrite {
+            set: &desc_set,
+            binding: binding as u32,
+            array_offset: 0,
+            descriptors: Some(Descriptor::Image(*image_view, Layout::General)),
+        })
+        .collect();
+
+    writes.push(DescriptorSetWrite {
+        set: &desc_set,
+        binding: sampler_binding,
+        array_offset: 0,
+        descriptors: Some(Descriptor::Sampler(&sampler)),
+    });
+
+    device.write_descriptor_sets(writes);
+
+    (set_layout, desc_set, sampler)
+}
+
+/// Points the images in a descriptor set made by create_image_bindings or
+/// create_multi_image_bindings at different image views, leaving the sampler as it is. The set must
+/// not be in use by the gpu.
+pub unsafe fn write_image_bindings<B: gfx_hal::Backend>(
+    device: &B::Device,
+    desc_set: &B::DescriptorSet,
+    image_views: &[&B::ImageView],
+) {
+    device.write_descriptor_sets(image_views.iter().enumerate().map(|(binding, image_view)| {
+        DescriptorSetWrite {
+            set: desc_set,
+            binding: binding as u32,
+            array_offset: 0,
+            descriptors: Some(Descriptor::Image(*image_view, Layout::General)),
+        }
+    }));
+}
@@ -32,11 +32,13 @@ pub unsafe fn create_buffer<B: gfx_hal::Backend>(
     buffer
 }
 
+/// The memory is returned along with the image, so it can be freed if the image is destroyed
+/// before the app closes.
 pub unsafe fn create_image<B: gfx_hal::Backend> (
     device: &B::Device,
     kind: Kind,
     format: Format,
-) -> B::Image {
+) -> (B::Image, B::Memory) {
     let mut image = device
         .create_image(
             kind,
@@ -63,5 +65,5 @@ pub unsafe fn create_image<B: gfx_hal::Backend> (
         )
         .expect("failed to bind memory");
 
-    image
+    (image, memory)
 }
\ No newline at end of file
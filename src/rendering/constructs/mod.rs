@@ -6,4 +6,5 @@ pub mod color_format;
 pub mod screen;
 pub mod create_buffer_bindings;
 pub mod pipeline;
-pub mod image_from_file;
\ No newline at end of file
+pub mod image_from_file;
+pub mod readback;
\ No newline at end of file
@@ -1,17 +1,21 @@
 use gfx_hal::prelude::*;
+use gfx_hal::pso::BlendState;
 use shaderc::ShaderKind;
 
-/// Create a pipeline with the given layout and shaders.
+/// Create a pipeline with the given layout and shaders. There is one entry in blends for each
+/// color attachment the fragment shader writes to, and None means the output replaces what was
+/// there.
 pub unsafe fn make_pipeline<B: gfx_hal::Backend>(
     device: &B::Device,
     render_pass: &B::RenderPass,
     pipeline_layout: &B::PipelineLayout,
     vertex_shader: &str,
     fragment_shader: &str,
+    blends: &[Option<BlendState>],
 ) -> B::GraphicsPipeline {
     use gfx_hal::pass::Subpass;
     use gfx_hal::pso::{
-        BlendState, ColorBlendDesc, ColorMask, EntryPoint, Face, GraphicsPipelineDesc,
+        ColorBlendDesc, ColorMask, EntryPoint, Face, GraphicsPipelineDesc,
         InputAssemblerDesc, Primitive, PrimitiveAssemblerDesc, Rasterizer, Specialization,
     };
 
@@ -59,10 +63,12 @@ pub unsafe fn make_pipeline<B: gfx_hal::Backend>(
         },
     );
 
-    pipeline_desc.blender.targets.push(ColorBlendDesc {
-        mask: ColorMask::ALL,
-        blend: Some(BlendState::ALPHA),
-    });
+    for blend in blends {
+        pipeline_desc.blender.targets.push(ColorBlendDesc {
+            mask: ColorMask::ALL,
+            blend: *blend,
+        });
+    }
 
     let pipeline = device
         .create_graphics_pipeline(&pipeline_desc, None)
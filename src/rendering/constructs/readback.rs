@@ -0,0 +1,113 @@
+use gfx_hal::adapter::Adapter;
+use gfx_hal::command::{BufferImageCopy, CommandBufferFlags, Level};
+use gfx_hal::format::Aspects;
+use gfx_hal::image::{Access, Extent, Layout, Offset, SubresourceLayers, SubresourceRange};
+use gfx_hal::memory::{Barrier, Dependencies, Properties, Segment};
+use gfx_hal::prelude::*;
+use gfx_hal::pso::PipelineStage;
+use gfx_hal::queue::QueueGroup;
+use gfx_hal::MemoryTypeId;
+
+/// Copies a 2D image with 4 bytes per pixel into memory that the cpu can see, waits for the copy to
+/// finish, and returns the bytes. The image is expected to be in the General layout, which is what
+/// the render pass leaves it in.
+pub unsafe fn read_image<B: gfx_hal::Backend>(
+    device: &B::Device,
+    adapter: &Adapter<B>,
+    command_pool: &mut B::CommandPool,
+    queue_group: &mut QueueGroup<B>,
+    image: &B::Image,
+    (width, height): (u32, u32),
+) -> Vec<u8> {
+    let size = (width * height * 4) as u64;
+
+    let mut buffer = device
+        .create_buffer(size, gfx_hal::buffer::Usage::TRANSFER_DST)
+        .expect("failed to create readback buffer");
+
+    let requirements = device.get_buffer_requirements(&buffer);
+
+    let memory_type = adapter
+        .physical_device
+        .memory_properties()
+        .memory_types
+        .iter()
+        .enumerate()
+        .position(|(id, memory_type)| {
+            requirements.type_mask & (1 << id) != 0
+                && memory_type
+                    .properties
+                    .contains(Properties::CPU_VISIBLE | Properties::COHERENT)
+        })
+        .expect("no cpu visible memory to read the image back into");
+
+    let memory = device
+        .allocate_memory(MemoryTypeId(memory_type), requirements.size)
+        .expect("failed to allocate memory for readback buffer");
+
+    device
+        .bind_buffer_memory(&memory, 0, &mut buffer)
+        .expect("failed to bind memory");
+
+    let mut command_buffer = command_pool.allocate_one(Level::Primary);
+    command_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
+
+    // make sure whatever was rendered on to the image has been written before it is copied.
+    command_buffer.pipeline_barrier(
+        PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::TRANSFER,
+        Dependencies::empty(),
+        &[Barrier::Image {
+            states: (Access::COLOR_ATTACHMENT_WRITE, Layout::General)
+                ..(Access::TRANSFER_READ, Layout::General),
+            target: image,
+            range: SubresourceRange {
+                aspects: Aspects::COLOR,
+                ..SubresourceRange::default()
+            },
+            families: None,
+        }],
+    );
+
+    command_buffer.copy_image_to_buffer(
+        image,
+        Layout::General,
+        &buffer,
+        &[BufferImageCopy {
+            buffer_offset: 0,
+            buffer_width: width,
+            buffer_height: height,
+            image_layers: SubresourceLayers {
+                aspects: Aspects::COLOR,
+                level: 0,
+                layers: 0..1,
+            },
+            image_offset: Offset::ZERO,
+            image_extent: Extent {
+                width,
+                height,
+                depth: 1,
+            },
+        }],
+    );
+
+    command_buffer.finish();
+
+    let fence = device.create_fence(false).expect("Out of memory");
+    queue_group.queues[0].submit_without_semaphores(vec![&command_buffer], Some(&fence));
+    device
+        .wait_for_fence(&fence, !0)
+        .expect("Out of memory or device lost");
+
+    let mapped = device
+        .map_memory(&memory, Segment::ALL)
+        .expect("failed to map readback memory");
+    let bytes = std::slice::from_raw_parts(mapped, size as usize).to_vec();
+    device.unmap_memory(&memory);
+
+    device.destroy_fence(fence);
+    command_pool.free(vec![command_buffer]);
+    device.destroy_buffer(buffer);
+    device.free_memory(memory);
+
+    bytes
+}
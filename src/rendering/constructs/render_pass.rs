@@ -5,22 +5,40 @@ use gfx_hal::pass::{
     Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, SubpassDesc
 };
 
+/// load_op decides what happens to what is already on the image. Clear wipes it, and Load keeps it
+/// so that new frames can be blended on top.
 pub unsafe fn create_render_pass<B: gfx_hal::Backend>(
     device: &B::Device,
     color_format: Format,
+    load_op: AttachmentLoadOp,
 ) -> B::RenderPass {
+    create_render_pass_with_attachments::<B>(device, &[(color_format, load_op)])
+}
+
+/// Creates a render pass with a single subpass that writes to every attachment given, in order.
+pub unsafe fn create_render_pass_with_attachments<B: gfx_hal::Backend>(
+    device: &B::Device,
+    attachments: &[(Format, AttachmentLoadOp)],
+) -> B::RenderPass {
+    let attachment_descs: Vec<Attachment> = attachments
+        .iter()
+        .map(|(format, load_op)| Attachment {
+            format: Some(*format),
+            samples: 1,
+            ops: AttachmentOps::new(*load_op, AttachmentStoreOp::Store),
+            stencil_ops: AttachmentOps::DONT_CARE,
+            layouts: Layout::General..Layout::General,
+        })
+        .collect();
+
+    let colors: Vec<_> = (0..attachments.len())
+        .map(|index| (index, Layout::ColorAttachmentOptimal))
+        .collect();
+
     device
-        .create_render_pass(&[
-            Attachment {
-                format: Some(color_format),
-                samples: 1,
-                ops: AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::Store),
-                stencil_ops: AttachmentOps::DONT_CARE,
-                layouts: Layout::General..Layout::General,
-            }
-        ], &[
+        .create_render_pass(&attachment_descs, &[
             SubpassDesc {
-                colors: &[(0, Layout::ColorAttachmentOptimal)],
+                colors: &colors,
                 depth_stencil: None,
                 inputs: &[],
                 resolves: &[],
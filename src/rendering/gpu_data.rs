@@ -3,24 +3,62 @@ use gfx_hal::pso::ShaderStageFlags;
 use std::fs::File;
 use std::ops::Range;
 
+/// The defaults for the graphics settings, which the golden images are rendered with.
+pub const DEFAULT_SAMPLES: u32 = 200;
+pub const DEFAULT_HIGHLIGHT_SAMPLES: u32 = 75;
+pub const DEFAULT_BOUNCE_DIST: u32 = 20;
+
 /// Data that needs to be pushed to the gpu, that isn't world data or a texture.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 pub struct GPUData {
     pub pos: [f32; 4],
-    pub dir: [f32; 4],
+    /// The camera basis, written by Camera::write_to. forward is scaled by the focal length, which
+    /// is what sets the field of view. right.w is how big an orthographic view is, or 0 when the
+    /// view is perspective.
+    pub camera_right: [f32; 4],
+    pub camera_up: [f32; 4],
+    pub camera_forward: [f32; 4],
 
-    pub palette: [[f32; 4]; 256],
+    /// The box of voxels that editing tools will affect, in voxel coordinates. min.w is 1.0 when
+    /// there is something selected, and 0.0 otherwise. normal is the face that new voxels will be
+    /// placed against.
+    pub selection_min: [f32; 4],
+    pub selection_max: [f32; 4],
+    pub selection_normal: [f32; 4],
 
-    pub text_to_show: [u32; 256],
+    pub palette: [[f32; 4]; 256],
 
     pub time: i32,
+    /// How many frames have already been blended into the temp image. See Accumulation.
+    pub accumulated_frames: u32,
 
     pub contrast: f32,
     pub brightness: f32,
     pub exposure: f32,
     pub hue: f32,
     pub saturation: f32,
+
+    /// How many passes of the denoiser to run on the temp image. 0 turns it off.
+    pub denoise_iterations: u32,
+    /// Which pass of the denoiser is being run. Set by render_draw, not by anything else.
+    pub denoise_step: u32,
+    /// How different two neighbouring colors can be before they stop being blurred together.
+    pub denoise_strength: f32,
+
+    /// Width divided by height of the window, so the picture isn't stretched when it is scaled up
+    /// from the render resolution.
+    pub aspect_ratio: f32,
+    /// These three are copied from the graphics settings, see GraphicsSettings.
+    pub samples: u32,
+    pub highlight_samples: u32,
+    pub bounce_dist: u32,
+
+    /// The last 64 frame times drawn by the hud, oldest first. Each bar is one byte, four to a
+    /// u32, where 255 is the top of the graph.
+    pub frame_time_graph: [u32; 16],
+    /// 1 when the hud's graph should be drawn.
+    pub show_hud: u32,
 }
 
 impl Default for GPUData {
@@ -50,15 +88,29 @@ impl Default for GPUData {
 
         GPUData {
             pos: [0.0, 0.0, 0.0, 0.0],
-            dir: [0.0, 0.0, 0.0, 0.0],
+            camera_right: [1.0, 0.0, 0.0, 0.0],
+            camera_up: [0.0, 1.0, 0.0, 0.0],
+            camera_forward: [0.0, 0.0, 1.0, 0.0],
+            selection_min: [0.0, 0.0, 0.0, 0.0],
+            selection_max: [0.0, 0.0, 0.0, 0.0],
+            selection_normal: [0.0, 0.0, 0.0, 0.0],
             palette,
-            text_to_show: [0; 256],
             time: 0,
+            accumulated_frames: 0,
             contrast: 0.0,
             brightness: 0.0,
             exposure: 1.0,
             hue: 0.0,
             saturation: 1.0,
+            denoise_iterations: 0,
+            denoise_step: 0,
+            denoise_strength: 0.3,
+            aspect_ratio: 1.0,
+            samples: DEFAULT_SAMPLES,
+            highlight_samples: DEFAULT_HIGHLIGHT_SAMPLES,
+            bounce_dist: DEFAULT_BOUNCE_DIST,
+            frame_time_graph: [0; 16],
+            show_hud: 0,
         }
     }
 }
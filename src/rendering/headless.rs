@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+
+use crate::options::StartupOptions;
+use crate::rendering::camera::Camera;
+use crate::rendering::reference::render_frames;
+use crate::rendering::resolution::RenderResolution;
+use crate::world::model_loader::Model;
+use crate::world::voxel_world::VoxelWorld;
+use crate::world::WorldUpdates;
+use crate::{Background, GPUData, ModelHolder};
+
+/// How long to wait for models to load before giving up.
+pub const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Used instead of create_window when the app is started with --headless. The world is built by
+/// running the app like normal, then the frame is rendered with the reference renderer, so this
+/// works on machines without a gpu. The process exits once the image has been written.
+pub fn run_headless(mut app: App) {
+    let options = app
+        .world
+        .get_resource::<StartupOptions>()
+        .cloned()
+        .unwrap_or_default();
+
+    app.insert_resource(GPUData::default());
+
+    match render_headless(&mut app, &options) {
+        Ok(()) => {
+            println!("wrote {}", options.out);
+            std::process::exit(0);
+        }
+        Err(err) => {
+            eprintln!("headless render failed: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn render_headless(app: &mut App, options: &StartupOptions) -> anyhow::Result<()> {
+    // --load is spawned in place of the tilemap when the app starts, so it is already loading.
+    wait_for_world(app)?;
+
+    let mut gpu_data = *app.world.get_resource::<GPUData>().unwrap();
+
+    if let Some([x, y, z, yaw]) = options.camera {
+        let camera = app.world.get_resource::<Camera>().unwrap();
+
+        Camera {
+            orientation: Quat::from_rotation_y(-yaw),
+            position: Vec3::new(x, y, z),
+            ..*camera
+        }
+        .write_to(&mut gpu_data);
+    }
+
+    let (width, height) = app.world.get_resource::<RenderResolution>().unwrap().base;
+    gpu_data.aspect_ratio = width as f32 / height as f32;
+
+    let world = app.world.get_resource::<VoxelWorld>().unwrap();
+    let frames = options.frames.unwrap_or(1);
+    let image = render_frames(world, &gpu_data, (width, height), 0, frames);
+
+    image.save_png(&options.out)
+}
+
+/// Updates the app until every model has been loaded and drawn into the world.
+fn wait_for_world(app: &mut App) -> anyhow::Result<()> {
+    let start = Instant::now();
+
+    loop {
+        app.update();
+
+        if is_world_loaded(&mut app.world)? {
+            return Ok(());
+        }
+
+        if start.elapsed() > LOAD_TIMEOUT {
+            anyhow::bail!("timed out waiting for models to load");
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// True once every model has been loaded and drawn into the world, and the world has been sent to
+/// the gpu. Fails if a model couldn't be loaded.
+pub fn is_world_loaded(world: &mut World) -> anyhow::Result<bool> {
+    let model_handles: Vec<Handle<Model>> = world
+        .query::<&ModelHolder>()
+        .iter(world)
+        .map(|model_holder| model_holder.handle().clone())
+        .collect();
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+
+    for handle in model_handles.iter() {
+        if asset_server.get_load_state(handle) == LoadState::Failed {
+            anyhow::bail!("a model failed to load");
+        }
+    }
+
+    let all_drawn = world
+        .query::<&Background>()
+        .iter(world)
+        .all(|background| background.has_been_drawn);
+    let all_uploaded = world.get_resource::<WorldUpdates>().unwrap().is_empty();
+
+    Ok(all_drawn && all_uploaded)
+}
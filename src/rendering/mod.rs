@@ -1,19 +1,30 @@
 use bevy::app::Events;
+use bevy::input::mouse::{MouseButtonInput, MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::input::touch::TouchInput;
 use bevy::prelude::*;
+use bevy::window::{
+    CursorEntered, CursorLeft, CursorMoved, ReceivedCharacter, WindowFocused, WindowId,
+};
 use gfx_hal::command::Level;
 use gfx_hal::device::Device;
 use gfx_hal::format::ChannelType;
-use gfx_hal::image::Kind;
+use gfx_hal::pass::AttachmentLoadOp;
 use gfx_hal::pool::CommandPoolCreateFlags;
 use gfx_hal::prelude::*;
+use gfx_hal::pso::BlendState;
 use gfx_hal::window::Extent2D;
 use gfx_hal::window::SwapchainConfig;
 use time::Instant;
-use winit::event::{DeviceEvent, Event, WindowEvent};
+use winit::dpi::PhysicalSize;
+use winit::event::{DeviceEvent, Event, MouseScrollDelta, WindowEvent};
 use winit::event_loop::ControlFlow;
 
-use crate::input::MousePos;
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::AddCommands;
+use crate::input::{MouseLook, MousePos};
+use crate::options::StartupOptions;
 use crate::rendering::constructs::image_from_file::create_image_buffer_from_file;
+use crate::settings::Settings;
 use crate::world::VOXEL_COUNT;
 use crate::CHUNK_COUNT;
 use constructs::color_format::*;
@@ -28,46 +39,146 @@ use gpu_data::GPUData;
 use render::render_draw;
 use render::RenderEvent;
 use resources::RenderInfo;
-
+use accumulation::Accumulation;
+use benchmark::Benchmark;
+use camera::bookmarks::{Bookmarks, BOOKMARKS_PATH};
+use camera::path::CameraPath;
+use camera::Camera;
+use render_targets::*;
+use resolution::RenderResolution;
+use screenshot::ScreenshotQueue;
+use text::Hud;
+use window_settings::*;
+
+pub mod accumulation;
+pub mod benchmark;
 pub mod bevy_to_winit;
+pub mod camera;
 pub mod gpu_data;
+pub mod headless;
 pub mod render;
 
 pub mod constructs;
 pub mod picture_info;
+pub mod reference;
+pub mod render_targets;
+pub mod resolution;
 pub mod resources;
+pub mod screenshot;
 pub mod shaders;
+pub mod text;
+pub mod window_settings;
+
+/// Systems in this stage run after everything in CoreStage::Update, right before the frame is
+/// drawn.
+pub const RENDER_STAGE: &str = "render";
+
+/// The size of the temp image that the voxel shader renders on to, until it is changed with the
+/// resolution command.
+pub const DEFAULT_RENDER_RESOLUTION: (u32, u32) = (200, 200);
 
 #[derive(Default)]
 pub struct CtklrRenderPlugin;
 
 impl Plugin for CtklrRenderPlugin {
     fn build(&self, app: &mut App) {
-        app.set_runner(|app| unsafe { create_window(app) })
-            .add_system(picture_info::setup_picture_data);
+        let options = app
+            .world
+            .get_resource::<StartupOptions>()
+            .cloned()
+            .unwrap_or_default();
+
+        if let Some((scene, camera_path)) = &options.benchmark {
+            app.insert_resource(Benchmark::new(
+                scene,
+                camera_path,
+                options.frames,
+                &options.report,
+            ));
+        }
+
+        if options.headless {
+            app.set_runner(headless::run_headless);
+        } else {
+            app.set_runner(|app| unsafe { create_window(app) });
+        }
+
+        let mut render_resolution = RenderResolution::default();
+
+        if let Some(resolution) = options.resolution {
+            render_resolution.set_base(resolution);
+        }
+
+        app.add_stage_after(CoreStage::Update, RENDER_STAGE, SystemStage::parallel())
+            .add_system_to_stage(CoreStage::PreUpdate, text::clear_hud)
+            .add_system(picture_info::setup_picture_data)
+            .add_system(screenshot::screenshot_input)
+            .add_system(accumulation::accumulation_commands)
+            .add_system(resolution::resolution_commands)
+            .add_system(resolution::update_dynamic_resolution)
+            .add_system(window_settings::window_commands)
+            .add_system(camera::camera_commands)
+            .add_system(camera::bookmarks::bookmark_commands)
+            .add_system(camera::path::camera_path_commands)
+            .add_system_to_stage(
+                RENDER_STAGE,
+                camera::path::play_camera_path.before("write_camera"),
+            )
+            .add_system_to_stage(
+                RENDER_STAGE,
+                camera::write_camera_data
+                    .label("write_camera")
+                    .before("accumulate"),
+            )
+            .add_system_to_stage(
+                RENDER_STAGE,
+                accumulation::update_accumulation.label("accumulate"),
+            )
+            .add_commands(picture_info::COMMANDS)
+            .add_commands(screenshot::COMMANDS)
+            .add_commands(accumulation::COMMANDS)
+            .add_commands(resolution::COMMANDS)
+            .add_commands(window_settings::COMMANDS)
+            .add_commands(camera::COMMANDS)
+            .add_commands(camera::bookmarks::COMMANDS)
+            .add_commands(camera::path::COMMANDS)
+            .insert_resource(Camera::default())
+            .insert_resource(Bookmarks::load(BOOKMARKS_PATH))
+            .insert_resource(CameraPath::default())
+            .insert_resource(ScreenshotQueue::default())
+            .insert_resource(render_resolution)
+            .insert_resource(Accumulation::default())
+            .insert_resource(Hud::default());
     }
 }
 
 unsafe fn create_window(mut app: App) {
-    const APP_NAME: &str = "gfx test";
-
     let gpu_data_buffer = GPUData::default();
 
     let event_loop = winit::event_loop::EventLoop::new();
 
-    let (logical_window_size, physical_window_size) = get_sizes(&event_loop, [512, 512]);
-    let surface_extent = Extent2D {
-        width: physical_window_size.width,
-        height: physical_window_size.height,
-    };
+    let settings = app.world.get_resource::<Settings>().unwrap().graphics.clone();
+    let (logical_window_size, _) = get_sizes(&event_loop, [settings.width, settings.height]);
     let window = winit::window::WindowBuilder::new()
-        .with_title(APP_NAME)
+        .with_title(&settings.title)
         .with_inner_size(logical_window_size)
-        .with_decorations(false)
+        .with_decorations(settings.decorations)
+        .with_fullscreen(to_winit_fullscreen(settings.fullscreen, event_loop.primary_monitor()))
         .build(&event_loop)
         .expect("Failed to create window");
 
-    let (width, height) = (200, 200);
+    // the window might not be the size that was asked for, like when it starts fullscreen.
+    let surface_extent = Extent2D {
+        width: window.inner_size().width,
+        height: window.inner_size().height,
+    };
+    let mut applied_fullscreen = settings.fullscreen;
+    // whether the cursor is grabbed and hidden for mouse look.
+    let mut cursor_grabbed = false;
+    // how long the last app.update() took, for the benchmark.
+    let mut update_time = std::time::Duration::ZERO;
+
+    let render_resolution = app.world.get_resource::<RenderResolution>().unwrap().size();
 
     let instance = backend::Instance::create("ctklr", 1).expect("Backend not supported");
     let surface = instance
@@ -86,16 +197,26 @@ unsafe fn create_window(mut app: App) {
     let mut command_buffer = command_pool.allocate_one(Level::Primary);
 
     // The word "temp" is used to described the texture that the shaders render on to. This texture
-    // is then upscaled and rendered to the main screen through the surface pipeline.
-    let temp_image = create_image::<backend::Backend>(
+    // is then upscaled and rendered to the main screen through the surface pipeline. The voxel
+    // shader also writes the normal, depth and color of whatever each pixel hit first, so that the
+    // denoiser can tell where the edges between surfaces are.
+    let render_targets = create_render_targets::<backend::Backend>(
         &device,
-        Kind::D2(width, height, 1, 1),
         surface_color_format,
+        render_resolution,
     );
-    let temp_image_view =
-        create_image_view::<backend::Backend>(&device, &temp_image, surface_color_format);
+    let views = &render_targets.image_views;
+
     let (temp_set_layout, temp_description_set, temp_sampler) =
-        create_image_bindings::<backend::Backend>(&device, &temp_image_view);
+        create_image_bindings::<backend::Backend>(&device, &views[0]);
+    let (gbuffer_set_layout, gbuffer_description_set, gbuffer_sampler) =
+        create_multi_image_bindings::<backend::Backend>(&device, &[&views[1], &views[2]]);
+
+    // Each denoiser pass reads from one of these and writes to the other.
+    let (denoise_set_layout_a, denoise_description_set_a, denoise_sampler_a) =
+        create_image_bindings::<backend::Backend>(&device, &views[3]);
+    let (denoise_set_layout_b, denoise_description_set_b, denoise_sampler_b) =
+        create_image_bindings::<backend::Backend>(&device, &views[4]);
 
     const SIZE_OF_WORLD_BUFFER: u64 = (VOXEL_COUNT * std::mem::size_of::<u32>()
         + CHUNK_COUNT * std::mem::size_of::<u32>()) as u64;
@@ -109,31 +230,52 @@ unsafe fn create_window(mut app: App) {
         &mut queue_group,
         "assets/images/font.png",
     );
+    let text_buffer = create_buffer::<backend::Backend>(&device, text::TEXT_BUFFER_SIZE);
     let (font_set_layout, font_description_set) =
-        create_buffer_bindings::<backend::Backend>(&device, &font_data);
+        create_storage_buffer_bindings::<backend::Backend>(&device, &[&font_data, &text_buffer]);
 
-    let render_pass = create_render_pass::<backend::Backend>(&device, surface_color_format);
+    let render_pass = create_render_pass::<backend::Backend>(
+        &device,
+        surface_color_format,
+        AttachmentLoadOp::Clear,
+    );
+    let trace_render_pass = create_render_pass_with_attachments::<backend::Backend>(
+        &device,
+        &[
+            (surface_color_format, AttachmentLoadOp::Load),
+            (NORMAL_DEPTH_FORMAT, AttachmentLoadOp::Clear),
+            (ALBEDO_FORMAT, AttachmentLoadOp::Clear),
+        ],
+    );
 
     let vertex_shader = shaders::VERTEX_CANVAS;
     let fragment_shader = shaders::VOXEL_RENDER;
     let post_processing_shader = shaders::POST_PROCESSING;
+    let denoise_shader = shaders::DENOISE;
 
     let temp_pipeline_layout = device
-        .create_pipeline_layout(&[world_set_layout], &[gpu_data_buffer.layout()])
+        .create_pipeline_layout(&[&world_set_layout], &[gpu_data_buffer.layout()])
         .expect("Out of memory");
     let surface_pipeline_layout = device
         .create_pipeline_layout(
-            &[temp_set_layout, font_set_layout],
+            &[&temp_set_layout, &font_set_layout],
+            &[gpu_data_buffer.layout()],
+        )
+        .expect("Out of memory");
+    let denoise_pipeline_layout = device
+        .create_pipeline_layout(
+            &[&denoise_set_layout_a, &gbuffer_set_layout],
             &[gpu_data_buffer.layout()],
         )
         .expect("Out of memory");
 
     let temp_pipeline = make_pipeline::<backend::Backend>(
         &device,
-        &render_pass,
+        &trace_render_pass,
         &temp_pipeline_layout,
         vertex_shader,
         fragment_shader,
+        &[Some(BlendState::ALPHA), None, None],
     );
 
     let surface_pipeline = make_pipeline::<backend::Backend>(
@@ -142,6 +284,16 @@ unsafe fn create_window(mut app: App) {
         &surface_pipeline_layout,
         vertex_shader,
         post_processing_shader,
+        &[Some(BlendState::ALPHA)],
+    );
+
+    let denoise_pipeline = make_pipeline::<backend::Backend>(
+        &device,
+        &render_pass,
+        &denoise_pipeline_layout,
+        vertex_shader,
+        denoise_shader,
+        &[None],
     );
 
     let submission_complete_fence = device.create_fence(true).expect("Out of memory");
@@ -153,18 +305,40 @@ unsafe fn create_window(mut app: App) {
         instance,
         surface,
         device,
-        render_passes: vec![render_pass],
-        pipeline_layouts: vec![temp_pipeline_layout, surface_pipeline_layout],
-        render_pipelines: vec![temp_pipeline, surface_pipeline],
+        render_passes: vec![render_pass, trace_render_pass],
+        pipeline_layouts: vec![
+            temp_pipeline_layout,
+            surface_pipeline_layout,
+            denoise_pipeline_layout,
+        ],
+        render_pipelines: vec![temp_pipeline, surface_pipeline, denoise_pipeline],
         compute_pipelines: vec![],
-        image_views: vec![temp_image_view],
+        images: render_targets.images,
+        image_memories: render_targets.memories,
+        image_views: render_targets.image_views,
+        descriptor_set_layouts: vec![
+            temp_set_layout,
+            world_set_layout,
+            font_set_layout,
+            gbuffer_set_layout,
+            denoise_set_layout_a,
+            denoise_set_layout_b,
+        ],
         description_sets: vec![
             temp_description_set,
             world_description_set,
             font_description_set,
+            gbuffer_description_set,
+            denoise_description_set_a,
+            denoise_description_set_b,
+        ],
+        samplers: vec![
+            temp_sampler,
+            gbuffer_sampler,
+            denoise_sampler_a,
+            denoise_sampler_b,
         ],
-        samplers: vec![temp_sampler],
-        buffers: vec![world_buffer],
+        buffers: vec![world_buffer, text_buffer],
         buffer_views: vec![],
         command_pool,
         submission_complete_fence,
@@ -173,7 +347,7 @@ unsafe fn create_window(mut app: App) {
         adapter,
         queue_group,
         surface_color_format,
-        render_resolution: (width, height),
+        render_resolution,
     };
 
     configure_swapchain(&mut resources);
@@ -193,6 +367,22 @@ unsafe fn create_window(mut app: App) {
 
                 *control_flow = ControlFlow::Exit
             }
+            WindowEvent::Resized(size) => {
+                let mut resources = app
+                    .world
+                    .get_resource_mut::<RenderInfo<backend::Backend>>()
+                    .unwrap();
+
+                resize_surface(&mut *resources, size);
+            }
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                let mut resources = app
+                    .world
+                    .get_resource_mut::<RenderInfo<backend::Backend>>()
+                    .unwrap();
+
+                resize_surface(&mut *resources, *new_inner_size);
+            }
             WindowEvent::KeyboardInput { ref input, .. } => {
                 let world = app.world.cell();
                 let mut keyboard_input_events = world
@@ -200,17 +390,140 @@ unsafe fn create_window(mut app: App) {
                     .unwrap();
                 keyboard_input_events.send(bevy_to_winit::convert_keyboard_input(input));
             }
+            WindowEvent::ReceivedCharacter(c) => {
+                let world = app.world.cell();
+                let mut received_character_events =
+                    world.get_resource_mut::<Events<ReceivedCharacter>>().unwrap();
+
+                received_character_events.send(ReceivedCharacter {
+                    id: WindowId::primary(),
+                    char: c,
+                });
+            }
             WindowEvent::CursorMoved { position, .. } => {
                 let world = app.world.cell();
                 let mut mouse_pos = world.get_resource_mut::<MousePos>().unwrap();
 
                 mouse_pos.0.x = position.x as f32 / window.inner_size().width as f32 * 2.0 - 1.0;
                 mouse_pos.0.y = position.y as f32 / window.inner_size().height as f32 * 2.0 - 1.0;
+
+                // bevy's cursor position is in logical pixels from the bottom left, like in
+                // bevy_winit.
+                let position = position.to_logical::<f32>(window.scale_factor());
+                let height = window.inner_size().to_logical::<f32>(window.scale_factor()).height;
+
+                let mut cursor_moved_events =
+                    world.get_resource_mut::<Events<CursorMoved>>().unwrap();
+                cursor_moved_events.send(CursorMoved {
+                    id: WindowId::primary(),
+                    position: Vec2::new(position.x, height - position.y),
+                });
+            }
+            WindowEvent::CursorEntered { .. } => {
+                let mut cursor_entered_events =
+                    app.world.get_resource_mut::<Events<CursorEntered>>().unwrap();
+
+                cursor_entered_events.send(CursorEntered {
+                    id: WindowId::primary(),
+                });
+            }
+            WindowEvent::CursorLeft { .. } => {
+                let mut cursor_left_events =
+                    app.world.get_resource_mut::<Events<CursorLeft>>().unwrap();
+
+                cursor_left_events.send(CursorLeft {
+                    id: WindowId::primary(),
+                });
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let mut mouse_button_events =
+                    app.world.get_resource_mut::<Events<MouseButtonInput>>().unwrap();
+
+                mouse_button_events.send(MouseButtonInput {
+                    button: bevy_to_winit::convert_mouse_button(button),
+                    state: bevy_to_winit::convert_element_state(state),
+                });
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let mut mouse_wheel_events =
+                    app.world.get_resource_mut::<Events<MouseWheel>>().unwrap();
+
+                mouse_wheel_events.send(match delta {
+                    MouseScrollDelta::LineDelta(x, y) => MouseWheel {
+                        unit: MouseScrollUnit::Line,
+                        x,
+                        y,
+                    },
+                    MouseScrollDelta::PixelDelta(delta) => MouseWheel {
+                        unit: MouseScrollUnit::Pixel,
+                        x: delta.x as f32,
+                        y: delta.y as f32,
+                    },
+                });
+            }
+            WindowEvent::Touch(touch) => {
+                let location = touch.location.to_logical(window.scale_factor());
+                let mut touch_events = app.world.get_resource_mut::<Events<TouchInput>>().unwrap();
+
+                touch_events.send(bevy_to_winit::convert_touch_input(touch, location));
+            }
+            WindowEvent::Focused(focused) => {
+                let mut focused_events =
+                    app.world.get_resource_mut::<Events<WindowFocused>>().unwrap();
+
+                focused_events.send(WindowFocused {
+                    id: WindowId::primary(),
+                    focused,
+                });
+
+                // some platforms let go of the cursor when the window loses focus, so it is grabbed
+                // again when it comes back.
+                if focused {
+                    cursor_grabbed = false;
+                }
             }
             _ => (),
         },
+        Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { delta },
+            ..
+        } => {
+            let world = app.world.cell();
+            let mut mouse_motion_events = world.get_resource_mut::<Events<MouseMotion>>().unwrap();
+
+            mouse_motion_events.send(MouseMotion {
+                delta: Vec2::new(delta.0 as f32, delta.1 as f32),
+            });
+        }
         Event::MainEventsCleared => {
+            if app.world.contains_resource::<Benchmark>() {
+                run_benchmark(&mut app);
+            }
+
+            resolution::apply_render_resolution::<backend::Backend>(&mut app.world);
+
+            let update_start = std::time::Instant::now();
             app.update();
+            update_time = update_start.elapsed();
+
+            // the window belongs to the event loop, so changes to it asked for by systems are
+            // applied here.
+            let fullscreen = app.world.get_resource::<Settings>().unwrap().graphics.fullscreen;
+
+            if fullscreen != applied_fullscreen {
+                window.set_fullscreen(to_winit_fullscreen(fullscreen, window.current_monitor()));
+                applied_fullscreen = fullscreen;
+            }
+
+            let mouse_look = app.world.get_resource::<MouseLook>().unwrap().enabled;
+
+            if mouse_look != cursor_grabbed {
+                // not every platform can grab the cursor, mouse look still works without it.
+                let _ = window.set_cursor_grab(mouse_look);
+                window.set_cursor_visible(!mouse_look);
+                cursor_grabbed = mouse_look;
+            }
+
             window.request_redraw();
         }
         Event::RedrawRequested(_) => {
@@ -226,20 +539,105 @@ unsafe fn create_window(mut app: App) {
                 .get_resource_mut::<RenderInfo<backend::Backend>>()
                 .unwrap();
 
+            if is_minimized(&*resources) {
+                return;
+            }
+
+            let render_start = std::time::Instant::now();
             let render_result = render_draw::<backend::Backend>(
                 &mut *resources,
                 &mut command_buffer,
                 &gpu_data_buffer,
+                &*world.get_resource::<Hud>().unwrap(),
             );
 
+            if let Some(mut benchmark) = world.get_resource_mut::<Benchmark>() {
+                benchmark.record(update_time, render_start.elapsed());
+            }
+
             if render_result.is_err() {
                 configure_swapchain(&mut *resources)
             }
+
+            let mut screenshots = world.get_resource_mut::<ScreenshotQueue>().unwrap();
+            let mut console = world.get_resource_mut::<Events<ConsoleMessage>>().unwrap();
+
+            for request in screenshots.0.drain(..) {
+                let message = match screenshot::save_screenshot(
+                    &mut *resources,
+                    &gpu_data_buffer,
+                    &request,
+                ) {
+                    Ok(path) => {
+                        ConsoleMessage::info(format!("saved screenshot to {}", path.display()))
+                    }
+                    Err(err) => {
+                        ConsoleMessage::error(format!("could not save screenshot: {}", err))
+                    }
+                };
+
+                console.send(message.for_request(request.control_request));
+            }
         }
         _ => (),
     });
 }
 
+/// Moves the benchmark along, and once it has measured every frame, writes the report and exits.
+unsafe fn run_benchmark(app: &mut App) {
+    let exit_code = match benchmark::update_benchmark(&mut app.world) {
+        Err(err) => {
+            eprintln!("benchmark failed: {}", err);
+            1
+        }
+        Ok(()) if app.world.get_resource::<Benchmark>().unwrap().is_finished() => {
+            let resolution = app
+                .world
+                .get_resource::<RenderInfo<backend::Backend>>()
+                .unwrap()
+                .render_resolution;
+
+            match benchmark::write_report(&app.world, resolution) {
+                Ok(path) => {
+                    println!("wrote {}", path.display());
+                    0
+                }
+                Err(err) => {
+                    eprintln!("could not write the benchmark report: {}", err);
+                    1
+                }
+            }
+        }
+        Ok(()) => return,
+    };
+
+    let resources = app
+        .world
+        .remove_resource::<RenderInfo<backend::Backend>>()
+        .unwrap();
+    resources.destroy_all();
+
+    std::process::exit(exit_code);
+}
+
+/// Called when the window changes size. The swapchain is only configured again while the window
+/// has an area, since one with a size of 0 can't be made.
+unsafe fn resize_surface<B: gfx_hal::Backend>(res: &mut RenderInfo<B>, size: PhysicalSize<u32>) {
+    res.surface_extent = Extent2D {
+        width: size.width,
+        height: size.height,
+    };
+
+    if !is_minimized(res) {
+        res.device.wait_idle().expect("Out of memory");
+        configure_swapchain(res);
+    }
+}
+
+fn is_minimized<B: gfx_hal::Backend>(res: &RenderInfo<B>) -> bool {
+    res.surface_extent.width == 0 || res.surface_extent.height == 0
+}
+
 unsafe fn configure_swapchain<B: gfx_hal::Backend>(res: &mut RenderInfo<B>) {
     let caps = res.surface.capabilities(&res.adapter.physical_device);
 
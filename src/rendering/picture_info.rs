@@ -1,26 +1,78 @@
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandSpec};
 use crate::debug::Command;
 use crate::GPUData;
 use bevy::prelude::*;
 
+/// Each denoiser pass reads pixels twice as far away as the last, so past this many the kernel is
+/// wider than the temp image.
+pub const MAX_DENOISE_ITERATIONS: u32 = 5;
+
+const VALUE: &[ArgSpec] = &[ArgSpec::new("value", ArgKind::Float)];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "contrast",
+        description: "sets the contrast, 0 leaves the picture as it is.",
+        args: VALUE,
+    },
+    CommandSpec {
+        name: "brightness",
+        description: "adds to every color, 0 leaves the picture as it is.",
+        args: VALUE,
+    },
+    CommandSpec {
+        name: "exposure",
+        description: "multiplies every color, 1 leaves the picture as it is.",
+        args: VALUE,
+    },
+    CommandSpec {
+        name: "saturation",
+        description: "multiplies the saturation, 1 leaves the picture as it is.",
+        args: VALUE,
+    },
+    CommandSpec {
+        name: "hue",
+        description: "turns the hue of every color, 0 to 1 is all the way around.",
+        args: VALUE,
+    },
+    CommandSpec {
+        name: "denoise",
+        description: "sets how many passes of the denoiser run, 0 turns it off.",
+        args: &[ArgSpec::new("iterations", ArgKind::UInt)],
+    },
+    CommandSpec {
+        name: "denoise_strength",
+        description: "sets how different neighbouring colors can be and still be blurred together.",
+        args: &[ArgSpec::new("strength", ArgKind::Float)],
+    },
+];
+
 pub fn setup_picture_data(
     mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
     mut gpu_data_buffer: ResMut<GPUData>,
 ) {
     for cmd in commands.iter() {
-        if cmd.is("contrast") {
-            gpu_data_buffer.contrast = cmd.parse_f32();
-        }
-        if cmd.is("brightness") {
-            gpu_data_buffer.brightness = cmd.parse_f32();
-        }
-        if cmd.is("exposure") {
-            gpu_data_buffer.exposure = cmd.parse_f32();
-        }
-        if cmd.is("saturation") {
-            gpu_data_buffer.saturation = cmd.parse_f32();
-        }
-        if cmd.is("hue") {
-            gpu_data_buffer.hue = cmd.parse_f32();
+        let gpu_data = &mut *gpu_data_buffer;
+
+        let result = match cmd.function.as_str() {
+            "contrast" => cmd.parse_arg(0).map(|value| gpu_data.contrast = value),
+            "brightness" => cmd.parse_arg(0).map(|value| gpu_data.brightness = value),
+            "exposure" => cmd.parse_arg(0).map(|value| gpu_data.exposure = value),
+            "saturation" => cmd.parse_arg(0).map(|value| gpu_data.saturation = value),
+            "hue" => cmd.parse_arg(0).map(|value| gpu_data.hue = value),
+            "denoise" => cmd.parse_arg::<u32>(0).map(|iterations| {
+                gpu_data.denoise_iterations = iterations.min(MAX_DENOISE_ITERATIONS)
+            }),
+            "denoise_strength" => cmd
+                .parse_arg(0)
+                .map(|value| gpu_data.denoise_strength = value),
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            console.send(cmd.error(err));
         }
     }
 }
@@ -0,0 +1,152 @@
+// Golden image tests. Each scene is loaded from the bundled models and rendered with the reference
+// renderer, then compared against a png saved in assets/golden. They run with `cargo test`, or
+// with `cargo run --release -- --golden`. Add `--bless` to the latter to write new golden images
+// after an intentional change to the renderer.
+
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::rendering::camera::Camera;
+use crate::rendering::reference::{render, RgbaImage};
+use crate::world::draw::draw_model::draw_static;
+use crate::world::model_loader::load_model;
+use crate::world::voxel_world::VoxelWorld;
+use crate::world::{chunk_index_to_position, CHUNK_SIZE};
+use crate::GPUData;
+
+const GOLDEN_DIR: &str = "assets/golden";
+/// Where the rendered image is written when it doesn't match, so it can be looked at.
+const FAILED_DIR: &str = "target/golden";
+const GOLDEN_RESOLUTION: (u32, u32) = (64, 64);
+const GOLDEN_SEED: u32 = 0;
+/// How far apart a channel can be before the pixel counts as different. This covers float
+/// differences between machines, not changes to the renderer.
+const CHANNEL_TOLERANCE: u8 = 2;
+
+struct GoldenScene {
+    model: &'static str,
+    yaw: f32,
+}
+
+const GOLDEN_SCENES: &[GoldenScene] = &[
+    GoldenScene { model: "block", yaw: -0.6 },
+    GoldenScene { model: "castle", yaw: -0.8 },
+    GoldenScene { model: "christmas", yaw: -0.4 },
+    GoldenScene { model: "menger", yaw: -0.7 },
+    GoldenScene { model: "monu16", yaw: -0.8 },
+    GoldenScene { model: "nature", yaw: -0.5 },
+];
+
+/// Returns true if every scene matched its golden image.
+pub fn run_golden_tests(bless: bool) -> bool {
+    let mut all_passed = true;
+
+    for scene in GOLDEN_SCENES {
+        let result = run_scene(scene, bless);
+
+        match &result {
+            Ok(()) => println!("golden {}: ok", scene.model),
+            Err(err) => println!("golden {}: FAILED, {}", scene.model, err),
+        }
+
+        all_passed &= result.is_ok();
+    }
+
+    all_passed
+}
+
+fn run_scene(scene: &GoldenScene, bless: bool) -> anyhow::Result<()> {
+    let mut world = VoxelWorld::default();
+    world.draw(&draw_static(&load_model(scene.model)?));
+
+    let mut gpu_data = GPUData::default();
+    Camera::looking(frame_world(&world, scene.yaw), scene.yaw, 0.0).write_to(&mut gpu_data);
+
+    let rendered = render(&world, &gpu_data, GOLDEN_RESOLUTION, GOLDEN_SEED);
+    let golden_path = Path::new(GOLDEN_DIR).join(format!("{}.png", scene.model));
+
+    if bless {
+        std::fs::create_dir_all(GOLDEN_DIR)?;
+        return rendered.save_png(golden_path);
+    }
+
+    let golden = RgbaImage::load_png(&golden_path).map_err(|err| {
+        anyhow::anyhow!("could not load {:?} ({}), run with --bless", golden_path, err)
+    })?;
+
+    let different_pixels = count_different_pixels(&rendered, &golden)?;
+
+    if different_pixels > 0 {
+        std::fs::create_dir_all(FAILED_DIR)?;
+        let failed_path = Path::new(FAILED_DIR).join(format!("{}.png", scene.model));
+        rendered.save_png(&failed_path)?;
+
+        anyhow::bail!("{} pixels are different, see {:?}", different_pixels, failed_path);
+    }
+
+    Ok(())
+}
+
+fn count_different_pixels(a: &RgbaImage, b: &RgbaImage) -> anyhow::Result<usize> {
+    if a.width != b.width || a.height != b.height {
+        anyhow::bail!(
+            "size is {}x{}, but the golden image is {}x{}",
+            a.width,
+            a.height,
+            b.width,
+            b.height
+        );
+    }
+
+    Ok(a.pixels
+        .chunks(4)
+        .zip(b.pixels.chunks(4))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(b.iter())
+                .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+        })
+        .count())
+}
+
+/// Finds a camera position looking at the middle of everything in the world from the given yaw,
+/// far enough back that all of it is in view.
+fn frame_world(world: &VoxelWorld, yaw: f32) -> Vec3 {
+    let mut min = Vec3::splat(f32::MAX);
+    let mut max = Vec3::splat(f32::MIN);
+
+    for (index, _) in world.filled_chunks.iter().enumerate().filter(|(_, filled)| **filled) {
+        let chunk_pos = chunk_index_to_position(index).as_vec3() * CHUNK_SIZE as f32;
+        min = min.min(chunk_pos);
+        max = max.max(chunk_pos + CHUNK_SIZE as f32);
+    }
+
+    if min.x > max.x {
+        return Vec3::ZERO;
+    }
+
+    let center = (min + max) / 2.0;
+    let forward = Vec3::new(-yaw.sin(), 0.0, yaw.cos());
+
+    center - forward * (max - min).length()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenes_match_golden_images() {
+        let failed: Vec<String> = GOLDEN_SCENES
+            .iter()
+            .filter_map(|scene| {
+                run_scene(scene, false)
+                    .err()
+                    .map(|err| format!("{}: {}", scene.model, err))
+            })
+            .collect();
+
+        assert!(failed.is_empty(), "{}", failed.join("\n"));
+    }
+}
@@ -0,0 +1,155 @@
+// A cpu version of the renderer. It is a straight port of the shaders, and is much too slow to use
+// every frame, but it gives the same picture without needing a gpu, which is what the golden image
+// tests are checked against.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::world::voxel_world::VoxelWorld;
+use crate::GPUData;
+use post_processing::{linear_to_srgb, post_process};
+use trace::trace_pixel;
+
+pub mod golden;
+pub mod post_processing;
+mod random;
+mod trace;
+
+/// An image with 4 bytes per pixel, in rgba order, starting at the top left.
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn save_png(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let file = File::create(path)?;
+
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+
+        Ok(())
+    }
+
+    pub fn load_png(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            anyhow::bail!("expected an 8 bit rgba png, got {:?}", info.color_type);
+        }
+
+        buf.truncate(info.buffer_size());
+
+        Ok(RgbaImage {
+            width: info.width,
+            height: info.height,
+            pixels: buf,
+        })
+    }
+}
+
+/// Renders the world the same way the temp and surface pipelines would, with gpu_data standing in
+/// for the push constants. The seed is mixed into the noise the same way the frame number is on
+/// the gpu, so the same seed always gives the same image.
+pub fn render(
+    world: &VoxelWorld,
+    gpu_data: &GPUData,
+    resolution: (u32, u32),
+    seed: u32,
+) -> RgbaImage {
+    render_frames(world, gpu_data, resolution, seed, 1)
+}
+
+/// Renders frame_count frames, with seeds counting up from first_seed, and averages them together
+/// before post processing. More frames means less noise.
+pub fn render_frames(
+    world: &VoxelWorld,
+    gpu_data: &GPUData,
+    (width, height): (u32, u32),
+    first_seed: u32,
+    frame_count: u32,
+) -> RgbaImage {
+    let mut pixels = vec![0; (width * height * 4) as usize];
+
+    let thread_count = std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1);
+    let rows_per_thread = (height as usize + thread_count - 1) / thread_count;
+
+    std::thread::scope(|scope| {
+        for (chunk_index, rows) in pixels
+            .chunks_mut(rows_per_thread.max(1) * width as usize * 4)
+            .enumerate()
+        {
+            scope.spawn(move || {
+                let first_row = chunk_index * rows_per_thread;
+
+                for (i, pixel) in rows.chunks_mut(4).enumerate() {
+                    let x = (i % width as usize) as u32;
+                    let y = (first_row + i / width as usize) as u32;
+
+                    let color = render_pixel(
+                        world,
+                        gpu_data,
+                        (x, y),
+                        (width, height),
+                        first_seed..first_seed + frame_count.max(1),
+                    );
+                    pixel.copy_from_slice(&color);
+                }
+            });
+        }
+    });
+
+    RgbaImage {
+        width,
+        height,
+        pixels,
+    }
+}
+
+fn render_pixel(
+    world: &VoxelWorld,
+    gpu_data: &GPUData,
+    (x, y): (u32, u32),
+    (width, height): (u32, u32),
+    seeds: Range<u32>,
+) -> [u8; 4] {
+    let frame_count = seeds.len() as f32;
+    let mut traced_sum = Vec3::ZERO;
+
+    for seed in seeds {
+        // this is what vertex_color is at the center of the pixel.
+        let frag_pos = Vec3::new(
+            (x as f32 + 0.5) / width as f32 * 2.0 - 1.0,
+            (y as f32 + 0.5) / height as f32 * 2.0 - 1.0,
+            seed as f32,
+        );
+
+        let traced = trace_pixel(world, gpu_data, frag_pos);
+
+        // the temp image can only hold values from 0 to 1. blending the frames together is what
+        // the accumulation on the gpu does.
+        traced_sum += traced.truncate().clamp(Vec3::ZERO, Vec3::ONE);
+    }
+
+    let color = post_process(gpu_data, traced_sum / frame_count);
+
+    [
+        (linear_to_srgb(color.x) * 255.0).round() as u8,
+        (linear_to_srgb(color.y) * 255.0).round() as u8,
+        (linear_to_srgb(color.z) * 255.0).round() as u8,
+        255,
+    ]
+}
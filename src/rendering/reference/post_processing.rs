@@ -0,0 +1,72 @@
+// A port of the color adjustments in post_processing.glsl, and the helpers they use from
+// vector_utils.glsl. The text overlay is not ported.
+
+use bevy::prelude::*;
+
+use crate::GPUData;
+
+fn fract(v: Vec3) -> Vec3 {
+    v - v.floor()
+}
+
+fn step(edge: f32, x: f32) -> f32 {
+    if x < edge {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+pub fn rgb_to_hsl(c: Vec3) -> Vec3 {
+    let k = Vec4::new(0.0, -1.0 / 3.0, 2.0 / 3.0, -1.0);
+    let p = Vec4::new(c.z, c.y, k.w, k.z).lerp(Vec4::new(c.y, c.z, k.x, k.y), step(c.z, c.y));
+    let q = Vec4::new(p.x, p.y, p.w, c.x).lerp(Vec4::new(c.x, p.y, p.z, p.x), step(p.x, c.x));
+
+    let d = q.x - q.w.min(q.y);
+    let e = 1.0e-10;
+    Vec3::new((q.z + (q.w - q.y) / (6.0 * d + e)).abs(), d / (q.x + e), q.x)
+}
+
+pub fn hsl_to_rgb(c: Vec3) -> Vec3 {
+    let k = Vec4::new(1.0, 2.0 / 3.0, 1.0 / 3.0, 3.0);
+    let p = (fract(Vec3::splat(c.x) + k.truncate()) * 6.0 - Vec3::splat(k.w)).abs();
+    c.z * Vec3::splat(k.x).lerp((p - Vec3::splat(k.x)).clamp(Vec3::ZERO, Vec3::ONE), c.y)
+}
+
+/// Takes a color as it was sampled from the temp image, and applies brightness, contrast, hue and
+/// saturation the same way the surface pipeline does.
+pub fn post_process(gpu_data: &GPUData, color: Vec3) -> Vec3 {
+    // brightness
+    let mut color = color + Vec3::splat(gpu_data.brightness);
+
+    // contrast
+    let factor = (259.0 * (gpu_data.contrast + 255.0)) / (255.0 * (259.0 - gpu_data.contrast));
+    color = (factor * (color - 128.0) + 128.0).clamp(Vec3::ZERO, Vec3::ONE);
+
+    // hue & saturation
+    let mut hsl = rgb_to_hsl(color);
+    hsl.x = (hsl.x + gpu_data.hue).fract();
+    hsl.y = (hsl.y * gpu_data.saturation).clamp(0.0, 1.0);
+
+    hsl_to_rgb(hsl)
+}
+
+/// The surface is an sRGB format, so the hardware encodes colors like this when they are written.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The inverse of linear_to_srgb, for when an sRGB image is sampled.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
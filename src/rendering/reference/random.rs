@@ -0,0 +1,22 @@
+// Ports of the hash functions in random.glsl that the path tracer uses. These need to match the
+// shader exactly, including the order of the swizzles, or the noise pattern will be different.
+
+use bevy::prelude::*;
+
+fn fract(v: Vec3) -> Vec3 {
+    v - v.floor()
+}
+
+/// 3 out, 2 in...
+pub fn hash32(p: Vec2) -> Vec3 {
+    let mut p3 = fract(Vec3::new(p.x, p.y, p.x) * Vec3::new(0.1031, 0.1030, 0.0973));
+    p3 += p3.dot(Vec3::new(p3.y, p3.x, p3.z) + 33.33);
+    fract((Vec3::new(p3.x, p3.x, p3.y) + Vec3::new(p3.y, p3.z, p3.z)) * Vec3::new(p3.z, p3.y, p3.x))
+}
+
+/// 3 out, 3 in...
+pub fn hash33(p3: Vec3) -> Vec3 {
+    let mut p3 = fract(p3 * Vec3::new(0.1031, 0.1030, 0.0973));
+    p3 += p3.dot(Vec3::new(p3.y, p3.x, p3.z) + 33.33);
+    fract((Vec3::new(p3.x, p3.x, p3.y) + Vec3::new(p3.y, p3.x, p3.x)) * Vec3::new(p3.z, p3.y, p3.x))
+}
@@ -0,0 +1,250 @@
+// A port of hit_in_direction.glsl, extract_color.glsl and the first half of voxel_render.frag.
+// Anything changed in those files should be changed here too, or the golden images will start
+// failing.
+
+use bevy::prelude::*;
+
+use crate::rendering::camera::screen_ray;
+use crate::rendering::reference::random::{hash32, hash33};
+use crate::world::voxel_world::{is_air, VoxelWorld};
+use crate::GPUData;
+
+const HIGHLIGHT_SENSITIVITY: f32 = 0.99;
+const FIRST_RAY_DIST: u32 = 400;
+
+const AIR: u32 = 0;
+
+const HIGHLIGHT_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+const HIGHLIGHT_OUTLINE_WIDTH: f32 = 0.06;
+
+pub struct Hit {
+    pub pos: Vec3,
+    pub normal: Vec3,
+    pub dist: u32,
+    pub unit_code: u32,
+}
+
+fn color_from(gpu_data: &GPUData, voxel: u32) -> Vec4 {
+    Vec4::from_slice(&gpu_data.palette[(voxel >> 24) as usize])
+}
+
+fn emission_from(voxel: u32) -> f32 {
+    ((voxel >> 20) & 0b1111) as f32 / 4.0
+}
+
+fn metallic_from(voxel: u32) -> f32 {
+    ((voxel >> 18) & 0b11) as f32 / 3.0
+}
+
+fn translucent_from(voxel: u32) -> f32 {
+    ((voxel >> 16) & 0b11) as f32 / 3.0
+}
+
+fn bool_to_f32(input: bool) -> f32 {
+    if input {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// In the shader, hit_in_direction first walks through chunks with filled_chunk_in_direction, but
+/// that always hands back the ray origin, so only the voxel walk is ported.
+pub fn hit_in_direction(world: &VoxelWorld, ro: Vec3, rd: Vec3, dist: u32, mask_color: u32) -> Hit {
+    let mut check_point = ro.floor();
+
+    let xy = rd.x / rd.y;
+    let yz = rd.y / rd.z;
+    let zx = rd.z / rd.x;
+    let xz = rd.x / rd.z;
+    let yx = rd.y / rd.x;
+    let zy = rd.z / rd.y;
+
+    let ray_unit_step_size = Vec3::new(
+        (1.0 + zx * zx + yx * yx).sqrt(),
+        (1.0 + xy * xy + zy * zy).sqrt(),
+        (1.0 + xz * xz + yz * yz).sqrt(),
+    );
+    let step = rd.signum();
+    let mut ray_length = (step * (check_point - ro) + (step / 2.0 + 0.5)) * ray_unit_step_size;
+
+    let mut comp;
+    for i in 0..dist {
+        comp = Vec3::new(
+            bool_to_f32(ray_length.x < ray_length.y && ray_length.x <= ray_length.z),
+            bool_to_f32(ray_length.y < ray_length.z && ray_length.y <= ray_length.x),
+            bool_to_f32(ray_length.z < ray_length.x && ray_length.z <= ray_length.y),
+        );
+
+        check_point += comp * step;
+
+        let unit_at_check_point = world.voxel_at(check_point.as_ivec3());
+        if unit_at_check_point != mask_color {
+            return Hit {
+                pos: ro + rd * ray_length.min_element(),
+                normal: -comp * step,
+                dist: i,
+                unit_code: unit_at_check_point,
+            };
+        }
+
+        ray_length += comp * ray_unit_step_size;
+    }
+
+    Hit {
+        pos: Vec3::ZERO,
+        normal: Vec3::ZERO,
+        dist: 0,
+        unit_code: 0,
+    }
+}
+
+/// frag_pos is frame_seed() in the shader.
+fn sample_at_hit(
+    world: &VoxelWorld,
+    gpu_data: &GPUData,
+    the_hit: &Hit,
+    num_samples: f32,
+    frag_pos: Vec3,
+) -> Vec4 {
+    if is_air(the_hit.unit_code) {
+        return Vec4::ONE.lerp(hash32(frag_pos.truncate() * 100.0).extend(1.0), 0.2);
+    }
+
+    let mut rand_vec = hash33(frag_pos + the_hit.pos);
+
+    let albedo_color = color_from(gpu_data, the_hit.unit_code);
+    let emission_amount = 1.0 + emission_from(the_hit.unit_code) * 0.5;
+    let hit_normal_mask = Vec3::new(
+        bool_to_f32(the_hit.normal.x == 0.0),
+        bool_to_f32(the_hit.normal.y == 0.0),
+        bool_to_f32(the_hit.normal.z == 0.0),
+    ) * (the_hit.normal.x + the_hit.normal.y + the_hit.normal.z);
+
+    // color_out in the shader never makes it into the returned color, so it isn't tracked here.
+    let mut light_amount = 0.0;
+
+    let mut i = 0.0;
+    while i < num_samples {
+        rand_vec -= Vec3::splat(0.5);
+        let to_light = (the_hit.normal + rand_vec.normalize() * hit_normal_mask * 5.0).normalize();
+        let to_light_hit =
+            hit_in_direction(world, the_hit.pos, to_light, gpu_data.bounce_dist, AIR);
+
+        if to_light_hit.unit_code == 0 {
+            light_amount += 1.0;
+        }
+
+        if light_amount / i > HIGHLIGHT_SENSITIVITY && i > gpu_data.highlight_samples as f32 {
+            return albedo_color * emission_amount;
+        }
+
+        rand_vec = hash33(rand_vec);
+        i += 1.0;
+    }
+
+    Vec4::new(0.04, 0.06, 0.0, 1.0).lerp(albedo_color, light_amount / num_samples)
+}
+
+fn highlight_selection(gpu_data: &GPUData, the_hit: &Hit, color: Vec4) -> Vec4 {
+    let selection_min = Vec4::from_slice(&gpu_data.selection_min);
+    let selection_max = Vec3::from_slice(&gpu_data.selection_max);
+    let selection_normal = Vec3::from_slice(&gpu_data.selection_normal);
+
+    if selection_min.w == 0.0 || is_air(the_hit.unit_code) {
+        return color;
+    }
+
+    let selection_min = selection_min.truncate();
+    let voxel = (the_hit.pos - the_hit.normal * 0.5).floor();
+
+    if voxel.cmplt(selection_min).any() || voxel.cmpge(selection_max).any() {
+        return color;
+    }
+
+    let size = selection_max - selection_min;
+    let from_min = the_hit.pos - selection_min;
+    let from_max = selection_max - the_hit.pos;
+
+    let edge_dist = from_min.min(from_max) + the_hit.normal.abs() * size;
+    let outline_width = HIGHLIGHT_OUTLINE_WIDTH * (size.max_element() / 4.0).max(1.0);
+
+    let mut amount = 0.25;
+
+    if the_hit.normal == selection_normal {
+        amount = 0.45;
+    }
+
+    if edge_dist.min_element() < outline_width {
+        amount = 0.9;
+    }
+
+    color.lerp(Vec4::from(HIGHLIGHT_COLOR), amount)
+}
+
+fn reflect(i: Vec3, n: Vec3) -> Vec3 {
+    i - 2.0 * n.dot(i) * n
+}
+
+/// The main() of voxel_render.frag. frag_pos is the position on the screen from -1 to 1, with the
+/// frame seed in z.
+pub fn trace_pixel(world: &VoxelWorld, gpu_data: &GPUData, frag_pos: Vec3) -> Vec4 {
+    let (ro, rd) = screen_ray(gpu_data, frag_pos.truncate());
+
+    let init_hit = hit_in_direction(world, ro, rd, FIRST_RAY_DIST, AIR);
+
+    let total_samples = gpu_data.samples as f32;
+    let gloss = metallic_from(init_hit.unit_code);
+    let translucent = translucent_from(init_hit.unit_code);
+
+    let mut output_color = if gloss > 0.0 {
+        let base_color =
+            sample_at_hit(world, gpu_data, &init_hit, total_samples * (1.0 - gloss), frag_pos);
+
+        let reflected_dir = reflect(rd, init_hit.normal);
+        let reflected_hit =
+            hit_in_direction(world, init_hit.pos, reflected_dir, FIRST_RAY_DIST, AIR);
+        let reflected_color =
+            sample_at_hit(world, gpu_data, &reflected_hit, total_samples * gloss, frag_pos);
+
+        base_color.lerp(reflected_color, gloss)
+    } else if translucent > 0.0 {
+        let base_color = sample_at_hit(
+            world,
+            gpu_data,
+            &init_hit,
+            total_samples * (1.0 - translucent),
+            frag_pos,
+        );
+
+        let ray_solid_through = hit_in_direction(
+            world,
+            init_hit.pos - init_hit.normal * 0.001,
+            rd,
+            FIRST_RAY_DIST,
+            init_hit.unit_code,
+        );
+        let ray_pass_through = hit_in_direction(
+            world,
+            ray_solid_through.pos + init_hit.normal * 0.001,
+            rd,
+            FIRST_RAY_DIST,
+            0,
+        );
+        let color_through_translucense = sample_at_hit(
+            world,
+            gpu_data,
+            &ray_pass_through,
+            total_samples * translucent,
+            frag_pos,
+        );
+
+        base_color.lerp(color_through_translucense, translucent)
+    } else {
+        sample_at_hit(world, gpu_data, &init_hit, total_samples, frag_pos)
+    };
+
+    output_color = highlight_selection(gpu_data, &init_hit, output_color);
+
+    output_color * gpu_data.exposure
+}
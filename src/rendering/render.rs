@@ -8,22 +8,38 @@ use time::Instant;
 
 use crate::GPUData;
 use crate::rendering::resources::RenderInfo;
+use crate::rendering::text::Hud;
 
 pub struct RenderEvent {
     pub time: Instant,
 }
 
+/// Which of res.images ends up being drawn to the screen. This is the temp image, unless the
+/// denoiser is on, in which case it is whichever denoise image the last pass wrote to.
+pub fn displayed_image_index(gpu_data: &GPUData) -> usize {
+    match gpu_data.denoise_iterations {
+        0 => 0,
+        iterations => 3 + (iterations as usize - 1) % 2,
+    }
+}
+
 pub fn render_draw<B: gfx_hal::Backend>(
     res: &mut RenderInfo<B>,
     command_buffer: &mut B::CommandBuffer,
     camera_data_buffer: &GPUData,
+    hud: &Hud,
 ) -> Result<(), ()> {
     let render_pass = &res.render_passes[0];
+    let trace_render_pass = &res.render_passes[1];
     let temp_pipeline_layout = &res.pipeline_layouts[0];
     let temp_pipeline = &res.render_pipelines[0];
     let temp_image_view = &res.image_views[0];
+    let normal_depth_image_view = &res.image_views[1];
+    let albedo_image_view = &res.image_views[2];
     let surface_pipeline_layout = &res.pipeline_layouts[1];
     let surface_pipeline = &res.render_pipelines[1];
+    let denoise_pipeline_layout = &res.pipeline_layouts[2];
+    let denoise_pipeline = &res.render_pipelines[2];
 
     unsafe {
         // We refuse to wait more than a second, to avoid hanging.
@@ -50,19 +66,28 @@ pub fn render_draw<B: gfx_hal::Backend>(
         }
     };
 
-    let (temp_framebuffer, surface_framebuffer) = unsafe {
+    let low_res_extent = Extent {
+        width: res.render_resolution.0,
+        height: res.render_resolution.1,
+        depth: 1
+    };
+
+    let (temp_framebuffer, denoise_framebuffers, surface_framebuffer) = unsafe {
         let temp = res.device
             .create_framebuffer(
-                render_pass,
-                vec![temp_image_view],
-                Extent {
-                    width: res.render_resolution.0,
-                    height: res.render_resolution.1,
-                    depth: 1
-                },
+                trace_render_pass,
+                vec![temp_image_view, normal_depth_image_view, albedo_image_view],
+                low_res_extent,
             )
             .unwrap();
 
+        // the denoiser passes take turns writing to these, reading from whatever was written last.
+        let denoise = [&res.image_views[3], &res.image_views[4]].map(|image_view| {
+            res.device
+                .create_framebuffer(render_pass, vec![image_view], low_res_extent)
+                .unwrap()
+        });
+
         let surface = res.device
             .create_framebuffer(
                 render_pass,
@@ -75,7 +100,7 @@ pub fn render_draw<B: gfx_hal::Backend>(
             )
             .unwrap();
 
-        (temp, surface)
+        (temp, denoise, surface)
     };
 
     let low_res_viewport = Viewport {
@@ -101,20 +126,36 @@ pub fn render_draw<B: gfx_hal::Backend>(
     unsafe {
         command_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
 
+        hud.upload::<B>(command_buffer, &res.buffers[1]);
+
         command_buffer.set_viewports(0, &[low_res_viewport.clone()]);
         command_buffer.set_scissors(0, &[low_res_viewport.rect]);
 
         command_buffer.bind_graphics_descriptor_sets(temp_pipeline_layout, 0, Some(&res.description_sets[1]), &[]);
 
+        // only the normal_depth and albedo attachments are cleared, the temp image is loaded so
+        // new frames can be blended over it.
         command_buffer.begin_render_pass(
-            render_pass,
+            trace_render_pass,
             &temp_framebuffer,
             low_res_viewport.rect,
-            &[ClearValue {
-                color: ClearColor {
-                    float32: [0.0, 0.0, 0.0, 1.0],
+            &[
+                ClearValue {
+                    color: ClearColor {
+                        float32: [0.0, 0.0, 0.0, 1.0],
+                    },
                 },
-            }],
+                ClearValue {
+                    color: ClearColor {
+                        float32: [0.5, 0.5, 0.5, 1.0],
+                    },
+                },
+                ClearValue {
+                    color: ClearColor {
+                        float32: [1.0, 1.0, 1.0, 1.0],
+                    },
+                },
+            ],
             SubpassContents::Inline,
         );
         command_buffer.bind_graphics_pipeline(temp_pipeline);
@@ -129,7 +170,43 @@ pub fn render_draw<B: gfx_hal::Backend>(
         command_buffer.draw(0..6, 0..1);
         command_buffer.end_render_pass();
 
-        command_buffer.bind_graphics_descriptor_sets(surface_pipeline_layout, 0, Some(&res.description_sets[0]), &[]);
+        let mut denoise_data = *camera_data_buffer;
+        let mut displayed_set = &res.description_sets[0];
+
+        for pass in 0..camera_data_buffer.denoise_iterations {
+            let output = pass as usize % 2;
+
+            command_buffer.bind_graphics_descriptor_sets(denoise_pipeline_layout, 0, Some(displayed_set), &[]);
+            command_buffer.bind_graphics_descriptor_sets(denoise_pipeline_layout, 1, Some(&res.description_sets[3]), &[]);
+
+            command_buffer.begin_render_pass(
+                render_pass,
+                &denoise_framebuffers[output],
+                low_res_viewport.rect,
+                &[ClearValue {
+                    color: ClearColor {
+                        float32: [0.0, 0.0, 0.0, 1.0],
+                    },
+                }],
+                SubpassContents::Inline,
+            );
+            command_buffer.bind_graphics_pipeline(denoise_pipeline);
+
+            denoise_data.denoise_step = pass;
+            command_buffer.push_graphics_constants(
+                denoise_pipeline_layout,
+                ShaderStageFlags::ALL,
+                0,
+                denoise_data.bytes(),
+            );
+
+            command_buffer.draw(0..6, 0..1);
+            command_buffer.end_render_pass();
+
+            displayed_set = &res.description_sets[4 + output];
+        }
+
+        command_buffer.bind_graphics_descriptor_sets(surface_pipeline_layout, 0, Some(displayed_set), &[]);
         command_buffer.bind_graphics_descriptor_sets(surface_pipeline_layout, 1, Some(&res.description_sets[2]), &[]);
 
         command_buffer.set_viewports(0, &[full_viewport.clone()]);
@@ -170,6 +247,7 @@ pub fn render_draw<B: gfx_hal::Backend>(
         );
 
         res.device.destroy_framebuffer(temp_framebuffer);
+        for framebuffer in denoise_framebuffers { res.device.destroy_framebuffer(framebuffer); }
         res.device.destroy_framebuffer(surface_framebuffer);
 
         if result.is_err() { return Err(()) }
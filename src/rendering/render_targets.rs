@@ -0,0 +1,78 @@
+use gfx_hal::format::Format;
+use gfx_hal::image::Kind;
+use gfx_hal::prelude::*;
+
+use crate::rendering::constructs::create_image_bindings::write_image_bindings;
+use crate::rendering::constructs::image_view::create_image_view;
+use crate::rendering::constructs::memory::create_image;
+use crate::rendering::resources::RenderInfo;
+
+/// Formats of the images the voxel shader writes next to the temp image for the denoiser. Depth
+/// needs more precision than 8 bits to tell nearby surfaces apart.
+pub const NORMAL_DEPTH_FORMAT: Format = Format::Rgba16Sfloat;
+pub const ALBEDO_FORMAT: Format = Format::Rgba8Unorm;
+
+/// Every image that is the size of the render resolution, in the order they are kept in
+/// RenderInfo: the temp image, normal_depth, albedo, and the two images the denoiser takes turns
+/// writing to.
+pub struct RenderTargets<B: gfx_hal::Backend> {
+    pub images: Vec<B::Image>,
+    pub memories: Vec<B::Memory>,
+    pub image_views: Vec<B::ImageView>,
+}
+
+pub unsafe fn create_render_targets<B: gfx_hal::Backend>(
+    device: &B::Device,
+    surface_color_format: Format,
+    (width, height): (u32, u32),
+) -> RenderTargets<B> {
+    let formats = [
+        surface_color_format,
+        NORMAL_DEPTH_FORMAT,
+        ALBEDO_FORMAT,
+        surface_color_format,
+        surface_color_format,
+    ];
+
+    let mut targets = RenderTargets {
+        images: Vec::with_capacity(formats.len()),
+        memories: Vec::with_capacity(formats.len()),
+        image_views: Vec::with_capacity(formats.len()),
+    };
+
+    for format in formats {
+        let (image, memory) = create_image::<B>(device, Kind::D2(width, height, 1, 1), format);
+
+        targets.image_views.push(create_image_view::<B>(device, &image, format));
+        targets.images.push(image);
+        targets.memories.push(memory);
+    }
+
+    targets
+}
+
+/// Replaces the render targets with ones of a new size, and points the descriptor sets that read
+/// from them at the new ones. Waits for the gpu to finish whatever it is doing first.
+pub unsafe fn resize_render_targets<B: gfx_hal::Backend>(
+    res: &mut RenderInfo<B>,
+    resolution: (u32, u32),
+) {
+    res.device.wait_idle().expect("Out of memory");
+
+    for view in res.image_views.drain(..) { res.device.destroy_image_view(view); }
+    for image in res.images.drain(..) { res.device.destroy_image(image); }
+    for memory in res.image_memories.drain(..) { res.device.free_memory(memory); }
+
+    let targets = create_render_targets::<B>(&res.device, res.surface_color_format, resolution);
+    let views = &targets.image_views;
+
+    write_image_bindings::<B>(&res.device, &res.description_sets[0], &[&views[0]]);
+    write_image_bindings::<B>(&res.device, &res.description_sets[3], &[&views[1], &views[2]]);
+    write_image_bindings::<B>(&res.device, &res.description_sets[4], &[&views[3]]);
+    write_image_bindings::<B>(&res.device, &res.description_sets[5], &[&views[4]]);
+
+    res.images = targets.images;
+    res.image_memories = targets.memories;
+    res.image_views = targets.image_views;
+    res.render_resolution = resolution;
+}
@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::rendering::accumulation::Accumulation;
+use crate::rendering::render_targets::resize_render_targets;
+use crate::rendering::resources::RenderInfo;
+use crate::rendering::DEFAULT_RENDER_RESOLUTION;
+use crate::GPUData;
+
+const MIN_RESOLUTION: u32 = 16;
+const MAX_RESOLUTION: u32 = 4096;
+
+/// The dynamic resolution controller never goes below this fraction of the base resolution.
+const MIN_SCALE: f32 = 0.25;
+
+/// Sizes are rounded to a multiple of this, so that small changes in scale don't recreate the
+/// render targets every frame.
+const SIZE_STEP: u32 = 8;
+
+/// The size of the temp image and everything else the voxel shader renders on to. The rays are
+/// spread out to match the aspect ratio of the window, so any size can be used without the picture
+/// being stretched.
+pub struct RenderResolution {
+    /// The size set with the `resolution` command.
+    pub base: (u32, u32),
+    /// What base is multiplied by. Only changed by the dynamic resolution controller.
+    pub scale: f32,
+    pub dynamic: Option<DynamicResolution>,
+}
+
+/// Scales the render resolution down when frames take longer than the target, and back up when
+/// there is time to spare.
+pub struct DynamicResolution {
+    pub target_frame_time: f32,
+    smoothed_frame_time: f32,
+}
+
+impl Default for RenderResolution {
+    fn default() -> Self {
+        RenderResolution {
+            base: DEFAULT_RENDER_RESOLUTION,
+            scale: 1.0,
+            dynamic: None,
+        }
+    }
+}
+
+impl RenderResolution {
+    pub fn size(&self) -> (u32, u32) {
+        let scale_axis = |size: u32| {
+            let scaled = (size as f32 * self.scale / SIZE_STEP as f32).round() as u32 * SIZE_STEP;
+
+            scaled.clamp(MIN_RESOLUTION, MAX_RESOLUTION)
+        };
+
+        (scale_axis(self.base.0), scale_axis(self.base.1))
+    }
+
+    pub fn set_base(&mut self, (width, height): (u32, u32)) {
+        self.base = (
+            width.clamp(MIN_RESOLUTION, MAX_RESOLUTION),
+            height.clamp(MIN_RESOLUTION, MAX_RESOLUTION),
+        );
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "resolution",
+        description: "sets the size of the picture the voxels are traced at.",
+        args: &[
+            ArgSpec::new("width", ArgKind::UInt),
+            ArgSpec::new("height", ArgKind::UInt),
+        ],
+    },
+    CommandSpec {
+        name: "dynamic_resolution",
+        description: "scales the resolution to keep frames under a time in ms, or turns that off.",
+        args: &[ArgSpec::new("ms|off", ArgKind::Text)],
+    },
+];
+
+/// `resolution <width> <height>` sets the base resolution. `dynamic_resolution <ms>` turns on the
+/// dynamic resolution controller with a target frame time, and `dynamic_resolution off` turns it
+/// off again.
+pub fn resolution_commands(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut resolution: ResMut<RenderResolution>,
+) {
+    for cmd in commands.iter() {
+        let result = if cmd.is("resolution") {
+            set_resolution(cmd, &mut resolution)
+        } else if cmd.is("dynamic_resolution") {
+            set_dynamic_resolution(cmd, &mut resolution)
+        } else {
+            Ok(())
+        };
+
+        if let Err(err) = result {
+            console.send(cmd.error(err));
+        }
+    }
+}
+
+fn set_resolution(cmd: &Command, resolution: &mut RenderResolution) -> Result<(), String> {
+    let width: u32 = cmd.parse_arg(0)?;
+    let height: u32 = cmd.parse_arg(1)?;
+
+    resolution.set_base((width, height));
+
+    Ok(())
+}
+
+fn set_dynamic_resolution(cmd: &Command, resolution: &mut RenderResolution) -> Result<(), String> {
+    if cmd.get_arg(0) == "off" {
+        resolution.dynamic = None;
+        resolution.scale = 1.0;
+        return Ok(());
+    }
+
+    let target_frame_time = cmd.parse_arg::<f32>(0)? / 1000.0;
+
+    resolution.dynamic = Some(DynamicResolution {
+        target_frame_time,
+        smoothed_frame_time: target_frame_time,
+    });
+
+    Ok(())
+}
+
+pub fn update_dynamic_resolution(time: Res<Time>, mut resolution: ResMut<RenderResolution>) {
+    let scale = resolution.scale;
+
+    let dynamic = match &mut resolution.dynamic {
+        Some(dynamic) => dynamic,
+        None => return,
+    };
+
+    dynamic.smoothed_frame_time = dynamic.smoothed_frame_time * 0.9 + time.delta_seconds() * 0.1;
+
+    // scaling down happens faster than scaling up, so that a slow spot is left quickly, and the
+    // resolution doesn't bounce between two sizes.
+    let new_scale = if dynamic.smoothed_frame_time > dynamic.target_frame_time * 1.1 {
+        scale * 0.95
+    } else if dynamic.smoothed_frame_time < dynamic.target_frame_time * 0.85 {
+        scale * 1.02
+    } else {
+        scale
+    };
+
+    resolution.scale = new_scale.clamp(MIN_SCALE, 1.0);
+}
+
+/// Called from the window's event loop before each update. Recreates the render targets if the
+/// resolution has changed, and keeps the aspect ratio of the rays matched to the surface.
+pub unsafe fn apply_render_resolution<B: gfx_hal::Backend>(world: &mut World) {
+    let world = world.cell();
+
+    let resolution = world.get_resource::<RenderResolution>().unwrap();
+    let mut res = world.get_resource_mut::<RenderInfo<B>>().unwrap();
+    let mut gpu_data = world.get_resource_mut::<GPUData>().unwrap();
+
+    // a minimized window has no height, so the last aspect ratio is kept until it comes back.
+    if res.surface_extent.height > 0 {
+        gpu_data.aspect_ratio = res.surface_extent.width as f32 / res.surface_extent.height as f32;
+    }
+
+    let size = resolution.size();
+
+    if size != res.render_resolution {
+        resize_render_targets(&mut *res, size);
+
+        world.get_resource_mut::<Accumulation>().unwrap().reset();
+    }
+}
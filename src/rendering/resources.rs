@@ -13,7 +13,12 @@ pub struct RenderInfo<B: gfx_hal::Backend> {
     pub pipeline_layouts: Vec<B::PipelineLayout>,
     pub render_pipelines: Vec<B::GraphicsPipeline>,
     pub compute_pipelines: Vec<B::ComputePipeline>,
+    /// The render targets, in the order described by RenderTargets.
+    pub images: Vec<B::Image>,
+    pub image_memories: Vec<B::Memory>,
     pub image_views: Vec<B::ImageView>,
+    /// Kept so that they can be destroyed, the pipeline layouts only borrow them.
+    pub descriptor_set_layouts: Vec<B::DescriptorSetLayout>,
     pub description_sets: Vec<B::DescriptorSet>,
     pub samplers: Vec<B::Sampler>,
     pub buffers: Vec<B::Buffer>,
@@ -36,8 +41,13 @@ impl<B: gfx_hal::Backend> RenderInfo<B> {
             for pipeline in self.render_pipelines {self.device.destroy_graphics_pipeline(pipeline); }
             for pipeline in self.compute_pipelines {self.device.destroy_compute_pipeline(pipeline); }
             for layout in self.pipeline_layouts {self.device.destroy_pipeline_layout(layout); }
+            for layout in self.descriptor_set_layouts {
+                self.device.destroy_descriptor_set_layout(layout);
+            }
             for pass in self.render_passes {self.device.destroy_render_pass(pass); }
             for view in self.image_views {self.device.destroy_image_view(view); }
+            for image in self.images {self.device.destroy_image(image); }
+            for memory in self.image_memories {self.device.free_memory(memory); }
             for sampler in self.samplers {self.device.destroy_sampler(sampler); }
             for buffer in self.buffers {self.device.destroy_buffer(buffer); }
             for view in self.buffer_views {self.device.destroy_buffer_view(view); }
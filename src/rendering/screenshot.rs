@@ -0,0 +1,224 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use gfx_hal::format::{ChannelType, Format};
+use time::OffsetDateTime;
+
+use crate::debug::control_server::ReplyLater;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::input::actions::{Action, ActionState};
+use crate::rendering::constructs::readback::read_image;
+use crate::rendering::reference::post_processing::{linear_to_srgb, post_process, srgb_to_linear};
+use crate::rendering::reference::RgbaImage;
+use crate::rendering::render::displayed_image_index;
+use crate::rendering::resources::RenderInfo;
+use crate::settings::Settings;
+use crate::GPUData;
+
+pub struct ScreenshotRequest {
+    pub name: Option<String>,
+    /// If true, the image is scaled up to the size of the window and has the surface pipeline's
+    /// color adjustments applied, instead of being the raw temp image.
+    pub post_processed: bool,
+    /// The directory it is saved to, paths.screenshots in the settings when it was asked for.
+    pub dir: String,
+    /// Where the message saying it was saved goes, see Command::control_request.
+    pub control_request: Option<u64>,
+}
+
+/// Screenshots waiting to be taken. These are taken right after the next frame is drawn.
+#[derive(Default)]
+pub struct ScreenshotQueue(pub Vec<ScreenshotRequest>);
+
+const NAME: &[ArgSpec] = &[ArgSpec::new("name", ArgKind::Text).optional()];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "screenshot",
+        description: "saves the picture before post processing to the screenshots directory.",
+        args: NAME,
+    },
+    CommandSpec {
+        name: "screenshot_post",
+        description: "saves the picture after post processing to the screenshots directory.",
+        args: NAME,
+    },
+];
+
+/// F12 takes a screenshot of the temp image, and Shift + F12 takes a post processed one, unless
+/// they have been rebound. These can also be taken with the `screenshot [name]` and
+/// `screenshot_post [name]` commands.
+pub fn screenshot_input(
+    actions: Res<ActionState>,
+    mut commands: EventReader<Command>,
+    mut screenshots: ResMut<ScreenshotQueue>,
+    mut reply_later: EventWriter<ReplyLater>,
+    settings: Res<Settings>,
+) {
+    // F12 is still held for Shift + F12, so the post processed binding is checked first.
+    let post_processed = actions.just_pressed(Action::ScreenshotPost);
+
+    if post_processed || actions.just_pressed(Action::Screenshot) {
+        screenshots.0.push(ScreenshotRequest {
+            name: None,
+            post_processed,
+            dir: settings.paths.screenshots.clone(),
+            control_request: None,
+        });
+    }
+
+    for cmd in commands.iter() {
+        if cmd.is("screenshot") || cmd.is("screenshot_post") {
+            screenshots.0.push(ScreenshotRequest {
+                name: cmd.arguments.get(0).cloned(),
+                post_processed: cmd.is("screenshot_post"),
+                dir: settings.paths.screenshots.clone(),
+                control_request: cmd.control_request,
+            });
+
+            // the screenshot is only saved after the frame is drawn.
+            if let Some(request) = cmd.control_request {
+                reply_later.send(ReplyLater(request));
+            }
+        }
+    }
+}
+
+/// Reads back the temp image, or the denoised one if the denoiser is on, and writes it to the
+/// screenshots directory. Returns where it was written.
+pub fn save_screenshot<B: gfx_hal::Backend>(
+    res: &mut RenderInfo<B>,
+    gpu_data: &GPUData,
+    request: &ScreenshotRequest,
+) -> anyhow::Result<PathBuf> {
+    let (width, height) = res.render_resolution;
+
+    let bytes = unsafe {
+        read_image::<B>(
+            &res.device,
+            &res.adapter,
+            &mut res.command_pool,
+            &mut res.queue_group,
+            &res.images[displayed_image_index(gpu_data)],
+            (width, height),
+        )
+    };
+
+    let mut image = RgbaImage {
+        width,
+        height,
+        pixels: to_srgb_rgba(bytes, res.surface_color_format),
+    };
+
+    if request.post_processed {
+        image = post_process_image(
+            &image,
+            gpu_data,
+            (res.surface_extent.width, res.surface_extent.height),
+        );
+    }
+
+    std::fs::create_dir_all(&request.dir)?;
+
+    let path = unused_path(
+        &request.dir,
+        &format!("{}_{}", request.name.as_deref().unwrap_or("screenshot"), timestamp()),
+    );
+
+    image.save_png(&path)?;
+
+    Ok(path)
+}
+
+/// The temp image uses the same format as the surface, which might have its channels in a
+/// different order, or might not be sRGB.
+fn to_srgb_rgba(mut bytes: Vec<u8>, format: Format) -> Vec<u8> {
+    let is_bgra = matches!(format, Format::Bgra8Srgb | Format::Bgra8Unorm);
+    let is_srgb = format.base_format().1 == ChannelType::Srgb;
+
+    for pixel in bytes.chunks_mut(4) {
+        if is_bgra {
+            pixel.swap(0, 2);
+        }
+
+        if !is_srgb {
+            for channel in pixel[0..3].iter_mut() {
+                *channel = (linear_to_srgb(*channel as f32 / 255.0) * 255.0).round() as u8;
+            }
+        }
+
+        pixel[3] = 255;
+    }
+
+    bytes
+}
+
+/// Does what the surface pipeline does on the cpu: stretches the temp image over the whole window
+/// and applies the color adjustments from post_processing.glsl. Text is not drawn.
+fn post_process_image(
+    image: &RgbaImage,
+    gpu_data: &GPUData,
+    (width, height): (u32, u32),
+) -> RgbaImage {
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            // the surface pipeline samples the temp image with a nearest filter.
+            let source_x = x * image.width / width;
+            let source_y = y * image.height / height;
+            let index = ((source_y * image.width + source_x) * 4) as usize;
+
+            let sampled = Vec3::new(
+                srgb_to_linear(image.pixels[index] as f32 / 255.0),
+                srgb_to_linear(image.pixels[index + 1] as f32 / 255.0),
+                srgb_to_linear(image.pixels[index + 2] as f32 / 255.0),
+            );
+
+            let color = post_process(gpu_data, sampled);
+
+            pixels.extend_from_slice(&[
+                (linear_to_srgb(color.x) * 255.0).round() as u8,
+                (linear_to_srgb(color.y) * 255.0).round() as u8,
+                (linear_to_srgb(color.z) * 255.0).round() as u8,
+                255,
+            ]);
+        }
+    }
+
+    RgbaImage {
+        width,
+        height,
+        pixels,
+    }
+}
+
+/// The timestamp only goes down to the second, so screenshots taken in the same second get a number
+/// on the end instead of writing over each other.
+fn unused_path(dir: &str, name: &str) -> PathBuf {
+    let dir = PathBuf::from(dir);
+    let mut path = dir.join(format!("{}.png", name));
+    let mut count = 2;
+
+    while path.exists() {
+        path = dir.join(format!("{}_{}.png", name, count));
+        count += 1;
+    }
+
+    path
+}
+
+fn timestamp() -> String {
+    let now = OffsetDateTime::now_utc();
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    )
+}
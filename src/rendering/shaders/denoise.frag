@@ -0,0 +1,93 @@
+layout(location = 0) out vec4 fragment_color;
+layout(location = 0) in vec4 vertex_color;
+
+// The image from the last pass, which is the temp image on the first iteration.
+layout(set = 0, binding = 0) uniform texture2D u_texture;
+layout(set = 0, binding = 1) uniform sampler u_sampler;
+
+// Written by the voxel shader along with the temp image. normal_depth has the normal of the first
+// hit in rgb, moved into the 0 to 1 range, and the distance to it in a.
+layout(set = 1, binding = 0) uniform texture2D g_normal_depth;
+layout(set = 1, binding = 1) uniform texture2D g_albedo;
+layout(set = 1, binding = 2) uniform sampler g_sampler;
+
+// The B3 spline, which is what the à-trous wavelet filter is usually built from.
+const float kernel[3] = float[](3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0);
+
+const float normal_power = 64.0;
+const float depth_sigma = 0.02;
+const float albedo_sigma = 0.05;
+// Rays that hit nothing have a normal of 0. Real normals are 1 long, so anything shorter than this
+// is taken to be a miss.
+const float min_normal_length = 0.5;
+
+vec4 color_at(ivec2 pixel) {
+    return texelFetch(sampler2D(u_texture, u_sampler), pixel, 0);
+}
+
+vec4 normal_depth_at(ivec2 pixel) {
+    return texelFetch(sampler2D(g_normal_depth, g_sampler), pixel, 0);
+}
+
+vec4 albedo_at(ivec2 pixel) {
+    return texelFetch(sampler2D(g_albedo, g_sampler), pixel, 0);
+}
+
+// One iteration of an edge aware à-trous filter. Each iteration spreads the 5x5 kernel out twice as
+// far as the last one, and neighbours only count if they are on a similar looking surface, so the
+// noise is blurred away without blurring the edges between voxels.
+void main() {
+    ivec2 size = textureSize(sampler2D(u_texture, u_sampler), 0);
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+
+    int step_width = 1 << pc.denoise_step;
+    float color_sigma = pc.denoise_strength / float(step_width);
+
+    vec4 center_color = color_at(pixel);
+    vec4 center_normal_depth = normal_depth_at(pixel);
+    vec3 center_normal = center_normal_depth.xyz * 2.0 - 1.0;
+    vec4 center_albedo = albedo_at(pixel);
+
+    vec4 color_sum = vec4(0.0);
+    float weight_sum = 0.0;
+
+    for (int y = -2; y <= 2; y++) {
+        for (int x = -2; x <= 2; x++) {
+            ivec2 sample_pixel = pixel + ivec2(x, y) * step_width;
+
+            if (any(lessThan(sample_pixel, ivec2(0))) || any(greaterThanEqual(sample_pixel, size))) {
+                continue;
+            }
+
+            vec4 sample_color = color_at(sample_pixel);
+            vec4 sample_normal_depth = normal_depth_at(sample_pixel);
+            vec3 sample_normal = sample_normal_depth.xyz * 2.0 - 1.0;
+            vec4 sample_albedo = albedo_at(sample_pixel);
+
+            vec3 color_diff = sample_color.rgb - center_color.rgb;
+            vec3 albedo_diff = sample_albedo.rgb - center_albedo.rgb;
+            float depth_diff = abs(sample_normal_depth.w - center_normal_depth.w);
+
+            float color_weight = exp(-dot(color_diff, color_diff) / max(color_sigma * color_sigma, 1e-6));
+            // misses don't have a surface to compare, so only the other weights count for them.
+            bool has_normals = length(sample_normal) > min_normal_length
+                && length(center_normal) > min_normal_length;
+            float normal_weight = has_normals
+                ? pow(max(dot(sample_normal, center_normal), 0.0), normal_power)
+                : 1.0;
+            float depth_weight = exp(-depth_diff / (depth_sigma * float(step_width)));
+            float albedo_weight = exp(-dot(albedo_diff, albedo_diff) / albedo_sigma);
+
+            float weight = kernel[abs(x)] * kernel[abs(y)]
+                * color_weight * normal_weight * depth_weight * albedo_weight;
+
+            color_sum += sample_color * weight;
+            weight_sum += weight;
+        }
+    }
+
+    // every weight can still round down to 0, then the pixel is left as it was.
+    vec4 filtered = weight_sum > 0.0 ? color_sum / weight_sum : center_color;
+
+    fragment_color = vec4(filtered.rgb, 1.0);
+}
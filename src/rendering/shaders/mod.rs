@@ -23,5 +23,12 @@ pub const POST_PROCESSING: &str = concat!(
     include_str!("version_header.glsl"),
     include_str!("pc_buffer.glsl"),
     include_str!("vector_utils.glsl"),
+    include_str!("text.glsl"),
     include_str!("post_processing.glsl"),
 );
+
+pub const DENOISE: &str = concat!(
+    include_str!("version_header.glsl"),
+    include_str!("pc_buffer.glsl"),
+    include_str!("denoise.frag"),
+);
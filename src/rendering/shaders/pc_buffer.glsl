@@ -1,16 +1,33 @@
 layout(push_constant) uniform PushConstants {
     vec4 camera_pos;
-    vec4 camera_dir;
+    vec4 camera_right;
+    vec4 camera_up;
+    vec4 camera_forward;
 
-    vec4 palette[256];
+    vec4 selection_min;
+    vec4 selection_max;
+    vec4 selection_normal;
 
-    uint text_to_show[256];
+    vec4 palette[256];
 
     int time;
+    uint accumulated_frames;
 
     float contrast;
     float brightness;
     float exposure;
     float hue;
     float saturation;
+
+    uint denoise_iterations;
+    uint denoise_step;
+    float denoise_strength;
+
+    float aspect_ratio;
+    uint samples;
+    uint highlight_samples;
+    uint bounce_dist;
+
+    uint frame_time_graph[16];
+    uint show_hud;
 } pc;
@@ -4,32 +4,32 @@ layout(location = 0) in vec4 vertex_color;
 layout(set = 0, binding = 0) uniform texture2D u_texture;
 layout(set = 0, binding = 1) uniform sampler u_sampler;
 
-const uint FONT_IMAGE_WIDTH = 280;
-const uint FONT_IMAGE_HEIGHT = 7;
-const uint FONT_IMAGE_AREA = FONT_IMAGE_WIDTH * FONT_IMAGE_HEIGHT;
-const uint CHAR_AMOUNT = 32;
-const uint CHAR_WIDTH = 7;
-
-layout(std430, set = 1, binding = 0) buffer FontBuffer {
-    int data[FONT_IMAGE_AREA];
-} font;
-
-bool is_letter_here() {
-    uint pos_index = uint(
-        floor((vertex_color.y / 2 + 0.5) * CHAR_AMOUNT) * CHAR_AMOUNT +
-        floor((vertex_color.x / 2 + 0.5) * CHAR_AMOUNT)
-    );
-
-    if (pos_index > 128) {
-        return false;
+// The frame time graph sits in the bottom left corner, one bar per frame, oldest on the left.
+const vec2 GRAPH_MIN = vec2(-1.0, 0.7);
+const vec2 GRAPH_MAX = vec2(-0.4, 1.0);
+const uint GRAPH_BARS = 64;
+// Where 60fps is on the graph, which goes up to 50ms.
+const float GRAPH_TARGET = 16.667 / 50.0;
+
+void draw_frame_time_graph() {
+    if (any(lessThan(vertex_color.xy, GRAPH_MIN)) || any(greaterThan(vertex_color.xy, GRAPH_MAX))) {
+        return;
     }
 
-    uint letter_index = pc.text_to_show[pos_index];
+    vec2 graph_pos = (vertex_color.xy - GRAPH_MIN) / (GRAPH_MAX - GRAPH_MIN);
+    // y goes down the screen, so the bars grow up from the bottom.
+    float height = 1.0 - graph_pos.y;
 
-    uint char_x = uint(letter_index * CHAR_WIDTH + fract(vertex_color.x / 2 * float(CHAR_AMOUNT)) * 7);
-    uint char_y = uint(fract(vertex_color.y / 2 * float(CHAR_AMOUNT)) * 7);
+    uint bar = min(uint(graph_pos.x * GRAPH_BARS), GRAPH_BARS - 1);
+    float bar_height = float((pc.frame_time_graph[bar / 4] >> (bar % 4 * 8)) & 0xff) / 255.0;
 
-    return font.data[char_y * FONT_IMAGE_WIDTH + char_x] != 0;
+    fragment_color.rgb *= 0.4;
+
+    if (height < bar_height) {
+        fragment_color.rgb = bar_height > GRAPH_TARGET ? vec3(1.0, 0.3, 0.2) : vec3(0.3, 1.0, 0.4);
+    } else if (abs(height - GRAPH_TARGET) < 0.005) {
+        fragment_color.rgb = vec3(1.0);
+    }
 }
 
 void main() {
@@ -49,7 +49,10 @@ void main() {
 
     fragment_color.rgb = hsl_to_rgb(hsl);
 
-    if (is_letter_here()) {
-        fragment_color = vec4(vec3(1.0) - fragment_color.xyz, 1.0);
+    if (pc.show_hud != 0) {
+        draw_frame_time_graph();
     }
+
+    vec4 text_color = text_color_here();
+    fragment_color.rgb = mix(fragment_color.rgb, text_color.rgb, text_color.a);
 }
@@ -0,0 +1,59 @@
+// Draws the text blocks queued with Hud::text. Every printable ASCII character has a glyph in the
+// font image, in order, starting from the space.
+
+const uint FONT_IMAGE_WIDTH = 570;
+const uint FONT_IMAGE_HEIGHT = 8;
+const uint FONT_IMAGE_AREA = FONT_IMAGE_WIDTH * FONT_IMAGE_HEIGHT;
+const uint GLYPH_WIDTH = 6;
+const uint GLYPH_HEIGHT = 8;
+
+// Must match MAX_TEXT_BLOCKS and MAX_TEXT_CHARS in text.rs.
+const uint MAX_TEXT_BLOCKS = 128;
+const uint MAX_TEXT_CHARS = 8192;
+
+layout(std430, set = 1, binding = 0) readonly buffer FontBuffer {
+    int data[FONT_IMAGE_AREA];
+} font;
+
+struct TextBlock {
+    vec4 color;
+    // the top left corner, in pixels from the top left of the window.
+    vec2 pos;
+    // how many pixels across each pixel of the font is.
+    float scale;
+    uint start;
+    uint len;
+};
+
+layout(std430, set = 1, binding = 1) readonly buffer TextBuffer {
+    uint block_count;
+    TextBlock blocks[MAX_TEXT_BLOCKS];
+    uint chars[MAX_TEXT_CHARS];
+} text;
+
+// The color of the text at this pixel. Alpha is 0 if there isn't any.
+vec4 text_color_here() {
+    vec4 color = vec4(0.0);
+
+    for (uint i = 0; i < min(text.block_count, MAX_TEXT_BLOCKS); i++) {
+        TextBlock block = text.blocks[i];
+        vec2 local = (gl_FragCoord.xy - block.pos) / block.scale;
+
+        if (local.x < 0 || local.y < 0 || local.y >= GLYPH_HEIGHT || local.x >= block.len * GLYPH_WIDTH) {
+            continue;
+        }
+
+        uint char_index = uint(local.x) / GLYPH_WIDTH;
+        uint glyph = text.chars[min(block.start + char_index, MAX_TEXT_CHARS - 1)];
+
+        uint font_x = glyph * GLYPH_WIDTH + uint(local.x) % GLYPH_WIDTH;
+        uint font_y = uint(local.y);
+
+        // blocks queued later are drawn over earlier ones.
+        if (font.data[font_y * FONT_IMAGE_WIDTH + font_x] != 0) {
+            color = block.color;
+        }
+    }
+
+    return color;
+}
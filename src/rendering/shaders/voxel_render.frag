@@ -1,17 +1,29 @@
-const float total_samples = 200;
-const float highlight_samples = 75;
 const float highlight_sensitivity = 0.99;
-const uint bounce_dist = 20;
 
 const uint AIR = 0;
 
+// The denoiser uses these to tell which neighbouring pixels are on the same surface.
+layout(location = 1) out vec4 normal_depth;
+layout(location = 2) out vec4 albedo;
+
+const float max_depth = 400;
+
+const vec4 highlight_color = vec4(1.0, 0.85, 0.2, 1.0);
+const float highlight_outline_width = 0.06;
+
+// The noise is seeded by the position on the screen and the frame number, so that each frame
+// blended into the temp image adds new information.
+vec3 frame_seed() {
+    return vec3(vertex_color.xy, float(pc.time));
+}
+
 vec4 sample_at_hit(hit the_hit, float num_samples) {
     if (is_air(the_hit.unit_code)) {
         return mix(vec4(1.0), vec4(hash32(vertex_color.xy * 100), 1.0), 0.2);
     }
 
     // 977 is a number that seemed to look good after i tried a bunch of prime numbers
-    vec3 rand_vec = hash33(vertex_color.xyz + the_hit.pos);
+    vec3 rand_vec = hash33(frame_seed() + the_hit.pos);
 
     vec4 albedo_color = color_from(the_hit.unit_code);
     float emission_amount = 1 + emission_from(the_hit.unit_code) * 0.5;
@@ -24,8 +36,8 @@ vec4 sample_at_hit(hit the_hit, float num_samples) {
     for (float i = 0; i < num_samples; i++) {
         rand_vec -= vec3(0.5);
         vec3 to_light = normalize(the_hit.normal + normalize(rand_vec) * hit_normal_mask * 5.0);
-        hit to_light_hit = hit_in_direction(the_hit.pos, to_light, bounce_dist, AIR);
-        float hit_dist = length(the_hit.pos - to_light_hit.pos) / float(bounce_dist);
+        hit to_light_hit = hit_in_direction(the_hit.pos, to_light, pc.bounce_dist, AIR);
+        float hit_dist = length(the_hit.pos - to_light_hit.pos) / float(pc.bounce_dist);
 
         if (to_light_hit.unit_code == 0) {
             color_out += albedo_color * emission_amount;
@@ -36,7 +48,7 @@ vec4 sample_at_hit(hit the_hit, float num_samples) {
             color_out += albedo_color * hit_dist + vec4(0.01, 0.02, 1.0, 1.0);
         }
 
-        if (light_amount / i > highlight_sensitivity && i > highlight_samples) {
+        if (light_amount / i > highlight_sensitivity && i > float(pc.highlight_samples)) {
             return albedo_color * emission_amount;
         }
 
@@ -46,20 +58,63 @@ vec4 sample_at_hit(hit the_hit, float num_samples) {
     return mix(vec4(0.04, 0.06, 0, 1.0), albedo_color, light_amount / num_samples);
 }
 
+// Tints the selected box, with a stronger tint on the face that new voxels will be placed
+// against, and outlines the edges of the box.
+vec4 highlight_selection(hit the_hit, vec4 color) {
+    if (pc.selection_min.w == 0 || is_air(the_hit.unit_code)) {
+        return color;
+    }
+
+    vec3 voxel = floor(the_hit.pos - the_hit.normal * 0.5);
+
+    if (any(lessThan(voxel, pc.selection_min.xyz)) || any(greaterThanEqual(voxel, pc.selection_max.xyz))) {
+        return color;
+    }
+
+    vec3 size = pc.selection_max.xyz - pc.selection_min.xyz;
+    vec3 from_min = the_hit.pos - pc.selection_min.xyz;
+    vec3 from_max = pc.selection_max.xyz - the_hit.pos;
+
+    // the axis of the normal is always on the edge, so it is pushed out of the way here.
+    vec3 edge_dist = min(from_min, from_max) + abs(the_hit.normal) * size;
+    float outline_width = highlight_outline_width * max(1.0, size_of_max_dimension(size) / 4.0);
+
+    float amount = 0.25;
+
+    if (the_hit.normal == pc.selection_normal.xyz) {
+        amount = 0.45;
+    }
+
+    if (size_of_min_dimension(edge_dist) < outline_width) {
+        amount = 0.9;
+    }
+
+    return mix(color, highlight_color, amount);
+}
+
 void main() {
-    float fov = 1.0;
+    vec3 screen_offset = pc.camera_right.xyz * vertex_color.x * pc.aspect_ratio - pc.camera_up.xyz * vertex_color.y;
+    float ortho_size = pc.camera_right.w;
 
-    vec3 rd = normalize(
-        vec3(
-            cos(pc.camera_dir.x) * vertex_color.x - sin(pc.camera_dir.x) * fov,
-            - vertex_color.y,
-            sin(pc.camera_dir.x) * vertex_color.x + cos(pc.camera_dir.x) * fov
-        )
-    );
-    vec3 ro = vec3(pc.camera_pos.x, pc.camera_pos.y, pc.camera_pos.z);
+    vec3 ro;
+    vec3 rd;
+
+    if (ortho_size > 0) {
+        // every ray goes the same way, from a spot on the plane the camera is on.
+        ro = pc.camera_pos.xyz + screen_offset * ortho_size;
+        rd = normalize(pc.camera_forward.xyz);
+    } else {
+        // camera_forward is scaled by the focal length, so it also sets the field of view.
+        ro = pc.camera_pos.xyz;
+        rd = normalize(pc.camera_forward.xyz + screen_offset);
+    }
 
     hit init_hit = hit_in_direction(ro, rd, 400, AIR);
 
+    normal_depth = vec4(init_hit.normal * 0.5 + 0.5, length(init_hit.pos - ro) / max_depth);
+    albedo = is_air(init_hit.unit_code) ? vec4(1.0) : color_from(init_hit.unit_code);
+
+    float total_samples = float(pc.samples);
     float gloss = metallic_from(init_hit.unit_code);
     float translucent = translucent_from(init_hit.unit_code);
     vec4 output_color;
@@ -84,5 +139,10 @@ void main() {
         output_color = sample_at_hit(init_hit, total_samples);
     }
 
+    output_color = highlight_selection(init_hit, output_color);
+
     fragment_color += output_color * pc.exposure;
+
+    // the temp pipeline blends this over what is already in the temp image.
+    fragment_color.a = 1.0 / (float(pc.accumulated_frames) + 1.0);
 }
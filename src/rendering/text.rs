@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use gfx_hal::buffer::Access;
+use gfx_hal::memory::{Barrier, Dependencies};
+use gfx_hal::prelude::*;
+use gfx_hal::pso::PipelineStage;
+
+/// Must match the sizes of the arrays in text.glsl.
+pub const MAX_TEXT_BLOCKS: usize = 128;
+pub const MAX_TEXT_CHARS: usize = 8192;
+
+/// The size of a glyph in the font image, in pixels. Each one has a gap on the right and bottom.
+pub const GLYPH_WIDTH: f32 = 6.0;
+pub const GLYPH_HEIGHT: f32 = 8.0;
+
+/// Where TextBuffer.chars starts in the buffer. It comes after the block count, which is padded out
+/// to 16 bytes, and the blocks.
+const CHARS_OFFSET: usize = 16 + MAX_TEXT_BLOCKS * std::mem::size_of::<TextBlock>();
+
+/// How big the buffer the text is uploaded to needs to be.
+pub const TEXT_BUFFER_SIZE: u64 =
+    (CHARS_OFFSET + MAX_TEXT_CHARS * std::mem::size_of::<u32>()) as u64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    pub color: Vec4,
+    /// How many pixels across each pixel of the font is.
+    pub scale: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            color: Vec4::ONE,
+            scale: 2.0,
+        }
+    }
+}
+
+impl TextStyle {
+    /// How far apart lines of text in this style are, in pixels.
+    pub fn line_height(&self) -> f32 {
+        GLYPH_HEIGHT * self.scale
+    }
+}
+
+/// One line of text, laid out the same way as TextBlock in text.glsl.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct TextBlock {
+    color: [f32; 4],
+    pos: [f32; 2],
+    scale: f32,
+    start: u32,
+    len: u32,
+    _padding: [u32; 3],
+}
+
+/// Text to draw over the picture. Anything can queue up text with Hud::text during the update, it
+/// is drawn that frame and then cleared, so text that should stay on screen has to be queued again
+/// every frame.
+#[derive(Default)]
+pub struct Hud {
+    blocks: Vec<TextBlock>,
+    chars: Vec<u32>,
+}
+
+impl Hud {
+    /// Queues text with its top left corner at pos, in pixels from the top left of the window.
+    pub fn text(&mut self, pos: Vec2, text: &str) {
+        self.text_styled(pos, text, TextStyle::default());
+    }
+
+    /// Each line of the text becomes its own block. Once the buffer is full, the rest is dropped.
+    pub fn text_styled(&mut self, pos: Vec2, text: &str, style: TextStyle) {
+        for (line_index, line) in text.lines().enumerate() {
+            let room = MAX_TEXT_CHARS - self.chars.len();
+
+            if self.blocks.len() == MAX_TEXT_BLOCKS || room == 0 {
+                return;
+            }
+
+            let start = self.chars.len();
+            self.chars.extend(line.chars().take(room).map(glyph_index));
+
+            self.blocks.push(TextBlock {
+                color: style.color.to_array(),
+                pos: [pos.x, pos.y + line_index as f32 * style.line_height()],
+                scale: style.scale,
+                start: start as u32,
+                len: (self.chars.len() - start) as u32,
+                _padding: [0; 3],
+            });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.chars.clear();
+    }
+
+    /// Records copying the queued text into the text buffer. Has to be done outside of a render
+    /// pass.
+    pub unsafe fn upload<B: gfx_hal::Backend>(
+        &self,
+        command_buffer: &mut B::CommandBuffer,
+        text_buffer: &B::Buffer,
+    ) {
+        let header = [self.blocks.len() as u32, 0, 0, 0];
+
+        command_buffer.update_buffer(text_buffer, 0, bytemuck::cast_slice(&header));
+
+        if !self.blocks.is_empty() {
+            command_buffer.update_buffer(text_buffer, 16, bytemuck::cast_slice(&self.blocks));
+        }
+
+        if !self.chars.is_empty() {
+            command_buffer.update_buffer(
+                text_buffer,
+                CHARS_OFFSET as u64,
+                bytemuck::cast_slice(&self.chars),
+            );
+        }
+
+        command_buffer.pipeline_barrier(
+            PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
+            Dependencies::empty(),
+            &[Barrier::AllBuffers(
+                Access::TRANSFER_WRITE..Access::SHADER_READ,
+            )],
+        );
+    }
+}
+
+/// Where a character is in the font image. The font has every printable ASCII character, anything
+/// else is drawn as a question mark.
+pub fn glyph_index(c: char) -> u32 {
+    match c {
+        ' '..='~' => c as u32 - ' ' as u32,
+        _ => '?' as u32 - ' ' as u32,
+    }
+}
+
+/// Text queued for the last frame has been drawn by the time the next update starts.
+pub fn clear_hud(mut hud: ResMut<Hud>) {
+    hud.clear();
+}
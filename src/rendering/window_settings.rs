@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::window::Fullscreen;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::input::actions::{Action, ActionState};
+use crate::settings::Settings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FullscreenMode {
+    #[serde(rename = "off", alias = "windowed")]
+    Windowed,
+    /// A window without decorations that covers the whole monitor.
+    Borderless,
+    /// Changes the video mode of the monitor to the biggest one it supports.
+    Exclusive,
+}
+
+fn parse_fullscreen_mode(text: &str) -> Result<FullscreenMode, String> {
+    match text {
+        "off" | "windowed" => Ok(FullscreenMode::Windowed),
+        "borderless" => Ok(FullscreenMode::Borderless),
+        "exclusive" => Ok(FullscreenMode::Exclusive),
+        other => Err(format!(
+            "expected off, borderless or exclusive, got {}",
+            other
+        )),
+    }
+}
+
+/// Turns a FullscreenMode into what winit needs to apply it on the given monitor.
+pub fn to_winit_fullscreen(
+    mode: FullscreenMode,
+    monitor: Option<MonitorHandle>,
+) -> Option<Fullscreen> {
+    match mode {
+        FullscreenMode::Windowed => None,
+        FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        FullscreenMode::Exclusive => monitor
+            .and_then(|monitor| biggest_video_mode(&monitor))
+            .map(Fullscreen::Exclusive),
+    }
+}
+
+fn biggest_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    monitor.video_modes().max_by_key(|mode| {
+        let size = mode.size();
+
+        (size.width * size.height, mode.refresh_rate())
+    })
+}
+
+pub const COMMANDS: &[CommandSpec] = &[CommandSpec {
+    name: "fullscreen",
+    description: "switches between a window and fullscreen, also toggled with F11.",
+    args: &[ArgSpec::new(
+        "mode",
+        ArgKind::Enum(&["off", "windowed", "borderless", "exclusive"]),
+    )],
+}];
+
+/// F11 switches between windowed and borderless fullscreen. `fullscreen off|borderless|exclusive`
+/// picks a mode directly. The window itself is changed by the event loop, which owns it.
+pub fn window_commands(
+    actions: Res<ActionState>,
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut settings: ResMut<Settings>,
+) {
+    let mut mode = None;
+
+    if actions.just_pressed(Action::ToggleFullscreen) {
+        mode = Some(match settings.graphics.fullscreen {
+            FullscreenMode::Windowed => FullscreenMode::Borderless,
+            _ => FullscreenMode::Windowed,
+        });
+    }
+
+    for cmd in commands.iter().filter(|cmd| cmd.is("fullscreen")) {
+        match parse_fullscreen_mode(cmd.get_arg(0)) {
+            Ok(new_mode) => mode = Some(new_mode),
+            Err(err) => console.send(cmd.error(format!("{}.", err))),
+        }
+    }
+
+    // settings is only borrowed mutably when the mode changes, since that marks it as changed,
+    // and everything that watches it would run again.
+    if let Some(mode) = mode.filter(|mode| *mode != settings.graphics.fullscreen) {
+        settings.graphics.fullscreen = mode;
+    }
+}
@@ -0,0 +1,405 @@
+// Tunables that used to be constants scattered around the code, kept in settings.toml so that they
+// last between runs. Keys are written as section.name, like graphics.samples. `get` and
+// `set <key> <value>` read and change them while the app runs, and `settings save` writes them back
+// to the file. Most are read every frame, so changing them takes effect straight away, the ones
+// that don't say so.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bevy::core::FixedTimestep;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use toml::Value;
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{AddCommands, ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::options::StartupOptions;
+use crate::rendering::gpu_data::{
+    GPUData, DEFAULT_BOUNCE_DIST, DEFAULT_HIGHLIGHT_SAMPLES, DEFAULT_SAMPLES,
+};
+use crate::rendering::window_settings::FullscreenMode;
+
+/// Where the settings are read from and saved to, relative to the working directory.
+pub const SETTINGS_PATH: &str = "settings.toml";
+
+const MAX_SAMPLES: u32 = 2000;
+
+/// Every key that get and set accept. Used to tab complete them.
+const KEYS: &[&str] = &[
+    "graphics.samples",
+    "graphics.highlight_samples",
+    "graphics.bounce_dist",
+    "graphics.title",
+    "graphics.width",
+    "graphics.height",
+    "graphics.decorations",
+    "graphics.fullscreen",
+    "input.move_speed",
+    "input.fast_move_speed",
+    "input.mouse_sensitivity",
+    "input.edit_raycast_dist",
+    "player.walk_speed",
+    "player.run_speed",
+    "player.crouch_speed",
+    "player.jump_speed",
+    "player.gravity",
+    "player.height",
+    "player.crouch_height",
+    "player.width",
+    "player.step_height",
+    "world.anchored_colors",
+    "world.max_island_voxels",
+    "debug.framerate_log_interval",
+    "paths.tilemap_model",
+    "paths.screenshots",
+];
+
+#[derive(Default)]
+pub struct CtklrSettingsPlugin;
+
+impl Plugin for CtklrSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let options = app
+            .world
+            .get_resource::<StartupOptions>()
+            .cloned()
+            .unwrap_or_default();
+
+        let mut settings = Settings::load(SETTINGS_PATH).unwrap_or_else(|err| {
+            println!("{}", err);
+            Settings::default()
+        });
+
+        if let Some(samples) = options.samples {
+            settings.graphics.samples = samples;
+        }
+
+        if options.fullscreen {
+            settings.graphics.fullscreen = FullscreenMode::Borderless;
+        }
+
+        app.insert_resource(settings)
+            .add_system(settings_commands)
+            .add_system(apply_graphics_settings)
+            .add_commands(COMMANDS);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub graphics: GraphicsSettings,
+    pub input: InputSettings,
+    pub player: PlayerSettings,
+    pub world: WorldSettings,
+    pub debug: DebugSettings,
+    pub paths: PathSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// How many rays the voxel shader sends from each pixel. Fewer is faster but noisier.
+    pub samples: u32,
+    /// Pixels that have been fully lit for this many samples stop early.
+    pub highlight_samples: u32,
+    /// How far the rays sent towards the light go, in voxels.
+    pub bounce_dist: u32,
+    /// The window title. Only read when the app starts, like width, height and decorations.
+    pub title: String,
+    /// The size of the window in logical pixels, so it is the same size on screens with a
+    /// different dpi.
+    pub width: u32,
+    pub height: u32,
+    pub decorations: bool,
+    /// off, borderless or exclusive. F11 and the fullscreen command change this too.
+    pub fullscreen: FullscreenMode,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            samples: DEFAULT_SAMPLES,
+            highlight_samples: DEFAULT_HIGHLIGHT_SAMPLES,
+            bounce_dist: DEFAULT_BOUNCE_DIST,
+            title: "gfx test".into(),
+            width: 512,
+            height: 512,
+            decorations: false,
+            fullscreen: FullscreenMode::Windowed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    /// How far the camera moves each frame, in voxels.
+    pub move_speed: f64,
+    /// How far the camera moves each frame while Return is held.
+    pub fast_move_speed: f64,
+    /// Radians turned per pixel the mouse moves, with mouse look on.
+    pub mouse_sensitivity: f64,
+    /// How far away tiles can be selected for editing, in tiles.
+    pub edit_raycast_dist: u32,
+    /// Replaces the default bindings of the actions it names, like
+    /// `move_forward = ["key W", "axis LeftStickY+"]`. See input::actions for what can be bound.
+    pub bindings: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        InputSettings {
+            move_speed: 0.02,
+            fast_move_speed: 1.0,
+            mouse_sensitivity: 0.003,
+            edit_raycast_dist: 100,
+            bindings: BTreeMap::new(),
+        }
+    }
+}
+
+/// How the character moves in play mode. Everything is in voxels and seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSettings {
+    pub walk_speed: f64,
+    /// How fast the character moves while move_fast is held.
+    pub run_speed: f64,
+    pub crouch_speed: f64,
+    /// How fast the character is going up as it leaves the ground.
+    pub jump_speed: f64,
+    pub gravity: f64,
+    pub height: f64,
+    pub crouch_height: f64,
+    pub width: f64,
+    /// Ledges up to this high are walked up without jumping.
+    pub step_height: f64,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        PlayerSettings {
+            walk_speed: 12.0,
+            run_speed: 24.0,
+            crouch_speed: 5.0,
+            jump_speed: 22.0,
+            gravity: 60.0,
+            height: 12.0,
+            crouch_height: 7.0,
+            width: 4.0,
+            step_height: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldSettings {
+    /// Palette indices of voxels that hold up whatever is attached to them, like the ground does.
+    pub anchored_colors: Vec<u8>,
+    /// Pieces knocked loose that are bigger than this stay where they are.
+    pub max_island_voxels: u32,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        WorldSettings {
+            anchored_colors: Vec::new(),
+            max_island_voxels: 4096,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebugSettings {
+    /// Seconds between each time the framerate is printed. Only read when the app starts.
+    pub framerate_log_interval: f64,
+}
+
+impl Default for DebugSettings {
+    fn default() -> Self {
+        DebugSettings {
+            framerate_log_interval: 0.2,
+        }
+    }
+}
+
+impl DebugSettings {
+    pub fn framerate_log_timestep(&self) -> FixedTimestep {
+        FixedTimestep::step(self.framerate_log_interval.max(0.01))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathSettings {
+    /// The model the tilemap is built out of, relative to the assets directory. Only read when the
+    /// app starts.
+    pub tilemap_model: String,
+    /// Where screenshots are saved.
+    pub screenshots: String,
+}
+
+impl Default for PathSettings {
+    fn default() -> Self {
+        PathSettings {
+            tilemap_model: "models/block.vox".into(),
+            screenshots: "screenshots".into(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings from a file. If there isn't one, the defaults are used, and anything
+    /// missing from the file keeps its default value.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Ok(Settings::default()),
+        };
+
+        toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+
+        let text = toml::to_string_pretty(self).map_err(|err| err.to_string())?;
+
+        std::fs::write(path, text)
+            .map_err(|err| format!("could not save {}, {}", path.display(), err))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::try_from(self).expect("settings can always be written as toml")
+    }
+
+    /// The value of a setting, written the same way as in the file.
+    pub fn get(&self, key: &str) -> Result<String, String> {
+        let mut value = self.to_value();
+
+        Ok(lookup(&mut value, key)?.to_string())
+    }
+
+    /// Changes a setting. The value is read as the same type that the setting already is.
+    pub fn set(&mut self, key: &str, text: &str) -> Result<(), String> {
+        let mut value = self.to_value();
+        let setting = lookup(&mut value, key)?;
+
+        let expected = |kind: &str| format!("{} expects {}, got {}.", key, kind, text);
+
+        *setting = match setting {
+            Value::Integer(_) => {
+                Value::Integer(text.parse().map_err(|_| expected("a whole number"))?)
+            }
+            Value::Float(_) => Value::Float(text.parse().map_err(|_| expected("a number"))?),
+            Value::Boolean(_) => {
+                Value::Boolean(text.parse().map_err(|_| expected("true or false"))?)
+            }
+            Value::Array(_) => parse_array(text).ok_or_else(|| expected("a list like [1, 2]"))?,
+            _ => Value::String(text.to_string()),
+        };
+
+        // numbers that don't fit, like negative sample counts, are caught here.
+        *self = value.try_into().map_err(|_| expected("a value in range"))?;
+
+        Ok(())
+    }
+}
+
+/// Reads a toml array like [1, 2]. toml can only read whole documents, so it is read as one.
+fn parse_array(text: &str) -> Option<Value> {
+    let document: Value = toml::from_str(&format!("value = {}", text)).ok()?;
+
+    document.get("value").filter(|value| value.is_array()).cloned()
+}
+
+/// Finds section.name in the settings, written as a toml table.
+fn lookup<'a>(value: &'a mut Value, key: &str) -> Result<&'a mut Value, String> {
+    key.split_once('.')
+        .and_then(move |(section, name)| value.get_mut(section)?.get_mut(name))
+        .filter(|setting| !setting.is_table())
+        .ok_or_else(|| format!("unknown setting {}, try get.", key))
+}
+
+const KEY: ArgSpec = ArgSpec::new("setting", ArgKind::Enum(KEYS));
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "get",
+        description: "prints a setting, or every setting.",
+        args: &[KEY.optional()],
+    },
+    CommandSpec {
+        name: "set",
+        description: "changes a setting until the app closes, unless it is saved.",
+        args: &[KEY, ArgSpec::new("value", ArgKind::Text)],
+    },
+    CommandSpec {
+        name: "settings",
+        description: "saves the settings to settings.toml, loads them from it, or resets them.",
+        args: &[ArgSpec::new(
+            "action",
+            ArgKind::Enum(&["save", "load", "reset"]),
+        )],
+    },
+];
+
+/// `get [key]`, `set <key> <value>` and `settings save|load|reset`.
+pub fn settings_commands(
+    mut commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut settings: ResMut<Settings>,
+) {
+    for cmd in commands.iter() {
+        let result = match cmd.function.as_str() {
+            "get" if cmd.arguments.is_empty() => {
+                let text = KEYS
+                    .iter()
+                    .map(|key| format!("{} = {}", key, settings.get(key).unwrap_or_default()))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                console.send(cmd.info(text));
+                Ok(())
+            }
+            "get" => settings
+                .get(cmd.get_arg(0))
+                .map(|value| console.send(cmd.info(value))),
+            "set" => settings.set(cmd.get_arg(0), cmd.get_arg(1)),
+            "settings" => match cmd.get_arg(0) {
+                "save" => settings.save(SETTINGS_PATH),
+                "load" => Settings::load(SETTINGS_PATH).map(|loaded| *settings = loaded),
+                "reset" => {
+                    *settings = Settings::default();
+                    Ok(())
+                }
+                other => Err(format!("expected save, load or reset, got {}.", other)),
+            },
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            console.send(cmd.error(err));
+        }
+    }
+}
+
+/// Copies the graphics settings into GPUData whenever they change.
+pub fn apply_graphics_settings(settings: Res<Settings>, mut gpu_data: ResMut<GPUData>) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    let graphics = &settings.graphics;
+
+    gpu_data.samples = graphics.samples.clamp(1, MAX_SAMPLES);
+    gpu_data.highlight_samples = graphics.highlight_samples;
+    gpu_data.bounce_dist = graphics.bounce_dist.max(1);
+}
@@ -15,7 +15,7 @@ pub fn draw_model(possible_model: Option<&Model>, model_holder: &ModelHolder) ->
     }
 }
 
-fn draw_static(model: &Model) -> Vec<ChunkData> {
+pub fn draw_static(model: &Model) -> Vec<ChunkData> {
     let mut output = Vec::new();
 
     for chunk_data in &model.voxels {
@@ -1,2 +1,2 @@
 pub mod draw_background;
-mod draw_model;
\ No newline at end of file
+pub mod draw_model;
\ No newline at end of file
@@ -8,8 +8,12 @@ pub struct Background {
 #[derive(Component)]
 pub struct Element;
 
+/// A model that moves on its own, see rigid_body.rs. It becomes a RigidBody once it has loaded.
 #[derive(Component)]
-pub struct Dynamic;
+pub struct Dynamic {
+    /// How fast it is going when it starts.
+    pub velocity: Vec3,
+}
 
 /// Not used as a component, instead used to transmit data about a given model.
 pub enum ModelType {
@@ -0,0 +1,321 @@
+// Keeps the world from having things floating in the air. When voxels are removed, the solid voxels
+// that were touching them are followed outwards to see if they are still held up, either by the
+// bottom of the world or by a voxel whose palette index is in world.anchored_colors. Pieces that
+// aren't are cut out of the static world and dropped as rigid bodies.
+//
+// Only the chunks around an edit are looked at. Anything that reaches past them is taken to be held
+// up, so that one removed voxel doesn't mean looking through the whole world. Pieces bigger than
+// world.max_island_voxels are also left alone.
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::settings::Settings;
+use crate::world::draw_type::{Dynamic, ModelType};
+use crate::world::rigid_body::{DynamicCells, RigidBody};
+use crate::world::voxel_world::{is_air, VoxelWorld};
+use crate::world::{queue_voxel, queued_voxel_at, WorldUpdates, CHANGE_WORLD, CHUNK_SIZE};
+
+/// How many chunks around an edit are looked at.
+const CHECK_RADIUS_CHUNKS: i32 = 1;
+
+const DIRECTIONS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [0, 1, 0],
+    [0, 0, 1],
+    [-1, 0, 0],
+    [0, -1, 0],
+    [0, 0, -1],
+];
+
+/// Sent when voxels from min up to, but not including, max have been removed from the world.
+pub struct VoxelsRemoved {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+#[derive(Default)]
+pub struct IntegrityPlugin;
+
+impl Plugin for IntegrityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<VoxelsRemoved>()
+            .add_system(detach_islands.label(CHANGE_WORLD));
+    }
+}
+
+/// A group of solid voxels that are connected to each other.
+struct Island {
+    voxels: HashMap<IVec3, u32>,
+    held_up: bool,
+}
+
+fn detach_islands(
+    mut removed: EventReader<VoxelsRemoved>,
+    mut commands: Commands,
+    mut world_updates: ResMut<WorldUpdates>,
+    mut bodies: Query<&mut RigidBody>,
+    voxel_world: Res<VoxelWorld>,
+    cells: Res<DynamicCells>,
+    settings: Res<Settings>,
+) {
+    let world_settings = &settings.world;
+    let max_voxels = world_settings.max_island_voxels as usize;
+    let is_anchored = |voxel: u32| {
+        let palette_index = (voxel >> 24) as u8;
+
+        world_settings.anchored_colors.contains(&palette_index)
+    };
+
+    for removed in removed.iter() {
+        // bodies resting on what was removed would otherwise sleep in the air.
+        wake_bodies_around(removed, &cells, &mut bodies);
+
+        let margin = IVec3::splat(CHECK_RADIUS_CHUNKS);
+        let region = (
+            (chunk_of(removed.min) - margin) * CHUNK_SIZE as i32,
+            (chunk_of(removed.max - IVec3::ONE) + margin + IVec3::ONE) * CHUNK_SIZE as i32,
+        );
+
+        // every voxel that has already been followed, so that each piece is only followed once.
+        let mut checked: HashSet<IVec3> = HashSet::default();
+        let mut islands = Vec::new();
+
+        let voxel_at = |pos: IVec3| {
+            let voxel = queued_voxel_at(&world_updates, &voxel_world, pos);
+
+            if is_air(voxel) || cells.contains(pos) {
+                None
+            } else {
+                Some(voxel)
+            }
+        };
+
+        for start in touching(removed.min, removed.max) {
+            if checked.contains(&start) {
+                continue;
+            }
+
+            let island = match voxel_at(start) {
+                Some(voxel) => follow(start, voxel, region, voxel_at, is_anchored, max_voxels),
+                None => continue,
+            };
+
+            checked.extend(island.voxels.keys().copied());
+
+            if !island.held_up {
+                islands.push(island);
+            }
+        }
+
+        for island in islands {
+            for pos in island.voxels.keys() {
+                queue_voxel(
+                    &mut world_updates,
+                    &voxel_world,
+                    *pos,
+                    0,
+                    ModelType::Dynamic,
+                );
+            }
+
+            if let Some(body) = RigidBody::from_voxels(island.voxels) {
+                commands
+                    .spawn()
+                    .insert(Transform::from_translation(body.position))
+                    .insert(Dynamic {
+                        velocity: Vec3::ZERO,
+                    })
+                    .insert(body);
+            }
+        }
+    }
+}
+
+/// Wakes every body that has a voxel in the removed box or next to it.
+fn wake_bodies_around(
+    removed: &VoxelsRemoved,
+    cells: &DynamicCells,
+    bodies: &mut Query<&mut RigidBody>,
+) {
+    let (min, max) = (removed.min - IVec3::ONE, removed.max + IVec3::ONE);
+
+    for z in min.z..max.z {
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let body = cells
+                    .body_at(IVec3::new(x, y, z))
+                    .and_then(|entity| bodies.get_mut(entity).ok());
+
+                if let Some(mut body) = body {
+                    body.wake();
+                }
+            }
+        }
+    }
+}
+
+fn chunk_of(pos: IVec3) -> IVec3 {
+    let chunk_size = CHUNK_SIZE as i32;
+
+    IVec3::new(
+        pos.x.div_euclid(chunk_size),
+        pos.y.div_euclid(chunk_size),
+        pos.z.div_euclid(chunk_size),
+    )
+}
+
+/// The voxels next to the sides of a box.
+fn touching(min: IVec3, max: IVec3) -> Vec<IVec3> {
+    let mut outside = Vec::new();
+
+    for z in min.z - 1..max.z + 1 {
+        for y in min.y - 1..max.y + 1 {
+            for x in min.x - 1..max.x + 1 {
+                let pos = IVec3::new(x, y, z);
+                let inside = pos.cmpge(min).all() && pos.cmplt(max).all();
+
+                if !inside {
+                    outside.push(pos);
+                }
+            }
+        }
+    }
+
+    outside
+}
+
+/// Follows the solid voxels connected to start, until something holds them up or there are no
+/// more to follow.
+fn follow(
+    start: IVec3,
+    start_voxel: u32,
+    (region_min, region_max): (IVec3, IVec3),
+    voxel_at: impl Fn(IVec3) -> Option<u32>,
+    is_anchored: impl Fn(u32) -> bool,
+    max_voxels: usize,
+) -> Island {
+    let mut voxels = HashMap::default();
+    let mut to_visit = vec![(start, start_voxel)];
+
+    voxels.insert(start, start_voxel);
+
+    let held_up = |voxels: HashMap<IVec3, u32>| Island {
+        voxels,
+        held_up: true,
+    };
+
+    while let Some((pos, voxel)) = to_visit.pop() {
+        if pos.y == 0 || is_anchored(voxel) {
+            return held_up(voxels);
+        }
+
+        for dir in DIRECTIONS {
+            let next = pos + IVec3::from(dir);
+
+            if voxels.contains_key(&next) {
+                continue;
+            }
+
+            let next_voxel = match voxel_at(next) {
+                Some(voxel) => voxel,
+                None => continue,
+            };
+
+            let in_region = next.cmpge(region_min).all() && next.cmplt(region_max).all();
+
+            if !in_region || voxels.len() >= max_voxels {
+                return held_up(voxels);
+            }
+
+            voxels.insert(next, next_voxel);
+            to_visit.push((next, next_voxel));
+        }
+    }
+
+    Island {
+        voxels,
+        held_up: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How big the region that is looked at is, from 0 up.
+    const REGION_SIZE: i32 = 32;
+    const ANCHOR: u32 = 7 << 24;
+
+    /// Follows the voxels connected to the first one given, in a world where those are the only
+    /// solid voxels.
+    fn follow_in(voxels: &[(IVec3, u32)], max_voxels: usize) -> Island {
+        let world: HashMap<IVec3, u32> = voxels.iter().copied().collect();
+        let voxel_at = |pos: IVec3| world.get(&pos).copied();
+        let is_anchored = |voxel: u32| voxel == ANCHOR;
+        let (start, start_voxel) = voxels[0];
+        let region = (IVec3::ZERO, IVec3::splat(REGION_SIZE));
+
+        follow(
+            start,
+            start_voxel,
+            region,
+            voxel_at,
+            is_anchored,
+            max_voxels,
+        )
+    }
+
+    /// A line of voxels along x, from start.
+    fn bar(start: IVec3, length: i32, voxel: u32) -> Vec<(IVec3, u32)> {
+        (0..length)
+            .map(|x| (start + IVec3::new(x, 0, 0), voxel))
+            .collect()
+    }
+
+    #[test]
+    fn floating_islands_are_detached() {
+        let island = follow_in(&bar(IVec3::new(4, 5, 4), 3, 1), 100);
+
+        assert!(!island.held_up);
+        assert_eq!(island.voxels.len(), 3);
+    }
+
+    #[test]
+    fn pieces_touching_the_ground_are_kept() {
+        let column: Vec<_> = (0..4).rev().map(|y| (IVec3::new(4, y, 4), 1)).collect();
+
+        assert!(follow_in(&column, 100).held_up);
+    }
+
+    #[test]
+    fn pieces_touching_an_anchored_color_are_kept() {
+        let mut voxels = bar(IVec3::new(4, 5, 4), 3, 1);
+        voxels.push((IVec3::new(7, 5, 4), ANCHOR));
+
+        assert!(follow_in(&voxels, 100).held_up);
+    }
+
+    #[test]
+    fn pieces_crossing_the_region_edge_are_held_up() {
+        let island = follow_in(&bar(IVec3::new(29, 5, 4), 5, 1), 100);
+
+        assert!(island.held_up);
+    }
+
+    #[test]
+    fn big_pieces_are_left_alone() {
+        let island = follow_in(&bar(IVec3::new(4, 5, 4), 10, 1), 4);
+
+        assert!(island.held_up);
+        assert_eq!(island.voxels.len(), 4);
+    }
+
+    #[test]
+    fn touching_is_the_shell_around_the_box() {
+        let outside = touching(IVec3::ZERO, IVec3::ONE);
+
+        assert_eq!(outside.len(), 26);
+        assert!(!outside.contains(&IVec3::ZERO));
+    }
+}
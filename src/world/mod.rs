@@ -8,16 +8,21 @@ use gfx_hal::Backend;
 
 use model_loader::ModelAssetPlugin;
 
+use crate::rendering::accumulation::Accumulation;
 use crate::rendering::resources::RenderInfo;
 use crate::world::draw_type::ModelType;
+use crate::world::voxel_world::VoxelWorld;
 use crate::{App, FixedTimestep, PHYSICS_TIME_STEP};
 
 pub mod draw;
 pub mod draw_type;
+pub mod integrity;
 pub mod load_elements;
 pub mod model_loader;
 pub mod model_type;
 mod parse_pec;
+pub mod rigid_body;
+pub mod voxel_world;
 
 pub const CHUNKS_X: usize = 16;
 pub const CHUNKS_Y: usize = 16;
@@ -26,6 +31,9 @@ pub const CHUNK_COUNT: usize = CHUNKS_X * CHUNKS_Y * CHUNKS_Z;
 pub const CHUNK_SIZE: usize = 16;
 pub const CHUNK_VOL: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
 pub const VOXEL_COUNT: usize = CHUNK_VOL * CHUNK_COUNT;
+pub const WORLD_SIZE_X: usize = CHUNKS_X * CHUNK_SIZE;
+pub const WORLD_SIZE_Y: usize = CHUNKS_Y * CHUNK_SIZE;
+pub const WORLD_SIZE_Z: usize = CHUNKS_Z * CHUNK_SIZE;
 
 /// The position of the filled_chunks value in the world buffer.
 pub const FILLED_CHUNKS_MEM_OFFSET: usize = VOXEL_COUNT * std::mem::size_of::<u32>();
@@ -51,6 +59,55 @@ pub fn chunk_position_to_index(pos: UVec3) -> usize {
 
 pub type WorldUpdates = HashMap<UVec3, Vec<(ModelType, [u32; CHUNK_VOL])>>;
 
+fn index_in_chunk(pos_in_chunk: UVec3) -> usize {
+    pos_in_chunk.x as usize
+        + pos_in_chunk.y as usize * CHUNK_SIZE
+        + pos_in_chunk.z as usize * CHUNK_SIZE * CHUNK_SIZE
+}
+
+/// Queues up a single voxel change. The whole chunk it is in gets re-uploaded by update_world.
+pub fn queue_voxel(
+    world_updates: &mut WorldUpdates,
+    voxel_world: &VoxelWorld,
+    pos: IVec3,
+    voxel: u32,
+    model_type: ModelType,
+) {
+    if !VoxelWorld::in_bounds(pos) {
+        return;
+    }
+
+    let pos = pos.as_uvec3();
+    let chunk_pos = pos / CHUNK_SIZE as u32;
+
+    // if this chunk has already been changed this frame, build on top of that change.
+    let updates_at_pos = world_updates
+        .entry(chunk_pos)
+        .or_insert_with(|| vec![(model_type, voxel_world.chunk(chunk_pos))]);
+
+    updates_at_pos[0].1[index_in_chunk(pos % CHUNK_SIZE as u32)] = voxel;
+}
+
+/// The voxel at a position, counting changes that have been queued up but not applied yet.
+pub fn queued_voxel_at(world_updates: &WorldUpdates, voxel_world: &VoxelWorld, pos: IVec3) -> u32 {
+    if !VoxelWorld::in_bounds(pos) {
+        return 0;
+    }
+
+    let chunk_pos = pos.as_uvec3() / CHUNK_SIZE as u32;
+    let pos_in_chunk = pos.as_uvec3() % CHUNK_SIZE as u32;
+
+    match world_updates.get(&chunk_pos) {
+        Some(updates_at_pos) => updates_at_pos[0].1[index_in_chunk(pos_in_chunk)],
+        None => voxel_world.voxel_at(pos),
+    }
+}
+
+/// Label for the systems that change the world. They queue up WorldUpdates.
+pub const CHANGE_WORLD: &str = "change_world";
+/// Label for update_world, which applies and uploads the queued WorldUpdates.
+pub const UPDATE_WORLD: &str = "update_world";
+
 #[derive(Default)]
 pub struct CtklrWorldPlugin;
 
@@ -60,31 +117,69 @@ pub struct ClearWorld;
 
 impl Plugin for CtklrWorldPlugin {
     fn build(&self, app: &mut App) {
-        static RENDER: &str = "render";
-        static CHANGE_WORLD: &str = "change_world";
-
         app.add_plugin(ModelAssetPlugin)
-            .add_stage_after(CoreStage::Update, RENDER, SystemStage::parallel())
+            .add_plugin(rigid_body::RigidBodyPlugin)
+            .add_plugin(integrity::IntegrityPlugin)
             .add_system(draw::draw_background::draw.label(CHANGE_WORLD))
-            .add_system(update_world::<backend::Backend>.after(CHANGE_WORLD))
+            .add_system(
+                update_world::<backend::Backend>
+                    .label(UPDATE_WORLD)
+                    .after(CHANGE_WORLD),
+            )
             .add_event::<ClearWorld>()
-            .insert_resource(WorldUpdates::default());
+            .insert_resource(WorldUpdates::default())
+            .insert_resource(VoxelWorld::default());
     }
 }
 
 pub fn update_world<B: gfx_hal::Backend>(
     mut world_updates: ResMut<WorldUpdates>,
-    mut command_buffer: ResMut<B::CommandBuffer>,
-    mut render_info: ResMut<RenderInfo<B>>,
+    command_buffer: Option<ResMut<B::CommandBuffer>>,
+    render_info: Option<ResMut<RenderInfo<B>>>,
+    mut voxel_world: ResMut<VoxelWorld>,
+    mut accumulation: ResMut<Accumulation>,
     mut should_clear: EventReader<ClearWorld>,
+) {
+    let should_clear = should_clear.iter().count() != 0;
+
+    if should_clear || !world_updates.is_empty() {
+        accumulation.reset();
+    }
+
+    if should_clear {
+        voxel_world.clear();
+    }
+
+    for (pos, update) in world_updates.iter() {
+        voxel_world.set_chunk(*pos, &update[0].1);
+    }
+
+    // when running headless there is no gpu, and the cpu copy of the world is all there is.
+    if let (Some(mut command_buffer), Some(mut render_info)) = (command_buffer, render_info) {
+        upload_world_updates::<B>(
+            &world_updates,
+            should_clear,
+            &mut *command_buffer,
+            &mut *render_info,
+        );
+    }
+
+    world_updates.clear();
+}
+
+fn upload_world_updates<B: gfx_hal::Backend>(
+    world_updates: &WorldUpdates,
+    should_clear: bool,
+    command_buffer: &mut B::CommandBuffer,
+    render_info: &mut RenderInfo<B>,
 ) {
     let world_buffer = &render_info.buffers[0];
 
     unsafe {
         command_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
 
-        if should_clear.iter().count() != 0 {
-            clear_world::<B>(world_buffer, &mut command_buffer);
+        if should_clear {
+            clear_world::<B>(world_buffer, command_buffer);
         }
 
         for (pos, update) in world_updates.iter() {
@@ -109,13 +204,11 @@ pub fn update_world<B: gfx_hal::Backend>(
         command_buffer.finish();
         render_info.queue_group.queues[0].submit_without_semaphores(vec![&*command_buffer], None);
     }
-
-    world_updates.clear();
 }
 
 unsafe fn clear_world<B: gfx_hal::Backend>(
     world_buffer: &B::Buffer,
-    mut command_buffer: &mut ResMut<B::CommandBuffer>,
+    command_buffer: &mut B::CommandBuffer,
 ) {
     command_buffer.fill_buffer(world_buffer, SubRange::WHOLE, 0);
 }
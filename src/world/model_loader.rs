@@ -30,6 +30,75 @@ impl Model {
     }
 }
 
+/// Loads assets/models/{model_name}.vox straight from the disk, without going through the asset
+/// server. This is for things that need a model before bevy is running, like the golden tests.
+pub fn load_model(model_name: &str) -> anyhow::Result<Model> {
+    let bytes = std::fs::read(format!("assets/models/{}.vox", model_name))?;
+
+    model_from_vox(&bytes, model_name)
+}
+
+/// Builds a model out of the bytes of a .vox file, along with the .pec file of the same name if
+/// there is one.
+pub fn model_from_vox(bytes: &[u8], model_name: &str) -> anyhow::Result<Model> {
+    //TODO: optimize
+    let pec = {
+        let pec_file_data = read_to_string(format!("assets/models/{}.pec", model_name))
+            .unwrap_or_else(|_| "".into());
+
+        parse_pec(pec_file_data)
+    };
+
+    let vox_data = vox_format::from_slice(bytes)?;
+
+    let mut chunks_to_load: Vec<UVec3> = Vec::new();
+
+    for v in &vox_data.models[0].voxels {
+        let chunk_pos = UVec3::new(
+            v.point.x as u32 / 16,
+            v.point.z as u32 / 16,
+            v.point.y as u32 / 16,
+        );
+        if !chunks_to_load.contains(&chunk_pos) {
+            chunks_to_load.push(chunk_pos);
+        }
+    }
+
+    let mut output_model = Model::new();
+
+    for c in &chunks_to_load {
+        let mut new_data = [0; CHUNK_VOL];
+
+        for v in &vox_data.models[0].voxels {
+            let chunk_pos = UVec3::new(
+                v.point.x as u32 / 16,
+                v.point.z as u32 / 16,
+                v.point.y as u32 / 16,
+            );
+
+            if c.x != chunk_pos.x || c.y != chunk_pos.y || c.z != chunk_pos.z {
+                continue;
+            }
+
+            let color_info = pec.get(&v.color_index.0).unwrap_or(&0);
+
+            let color = (v.color_index.0 as u32) << 24;
+
+            new_data[v.point.x as usize % CHUNK_SIZE
+                + v.point.z as usize % CHUNK_SIZE * CHUNK_SIZE
+                + v.point.y as usize % CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE] =
+                color + color_info + 1;
+        }
+
+        output_model.voxels.push(ChunkData {
+            pos: *c,
+            data: new_data,
+        });
+    }
+
+    Ok(output_model)
+}
+
 #[derive(Default)]
 pub struct ModelLoader;
 
@@ -40,72 +109,18 @@ impl AssetLoader for ModelLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            //TODO: optimize
-            let pec = {
-                let model_path = load_context
-                    .path()
-                    .file_name()
-                    .expect("could not get file name for model")
-                    .to_str()
-                    .expect("could not convert file name to string");
-
-                // remove .vox file extension by cutting out the last 4 characters
-
-                let model_name = &model_path[0..model_path.len() - 4];
-
-                let pec_file_data = read_to_string(format!("assets/models/{}.pec", model_name))
-                    .unwrap_or_else(|_| "".into());
-
-                parse_pec(pec_file_data)
-            };
-
-            let vox_data =
-                vox_format::from_slice(bytes).expect("could not parse vox data from file");
-
-            let mut chunks_to_load: Vec<UVec3> = Vec::new();
-
-            for v in &vox_data.models[0].voxels {
-                let chunk_pos = UVec3::new(
-                    v.point.x as u32 / 16,
-                    v.point.z as u32 / 16,
-                    v.point.y as u32 / 16,
-                );
-                if !chunks_to_load.contains(&chunk_pos) {
-                    chunks_to_load.push(chunk_pos);
-                }
-            }
+            let model_path = load_context
+                .path()
+                .file_name()
+                .expect("could not get file name for model")
+                .to_str()
+                .expect("could not convert file name to string");
 
-            let mut output_model = Model::new();
+            // remove .vox file extension by cutting out the last 4 characters
 
-            for c in &chunks_to_load {
-                let mut new_data = [0; CHUNK_VOL];
+            let model_name = &model_path[0..model_path.len() - 4];
 
-                for v in &vox_data.models[0].voxels {
-                    let chunk_pos = UVec3::new(
-                        v.point.x as u32 / 16,
-                        v.point.z as u32 / 16,
-                        v.point.y as u32 / 16,
-                    );
-
-                    if c.x != chunk_pos.x || c.y != chunk_pos.y || c.z != chunk_pos.z {
-                        continue;
-                    }
-
-                    let color_info = pec.get(&v.color_index.0).unwrap_or(&0);
-
-                    let color = (v.color_index.0 as u32) << 24;
-
-                    new_data[v.point.x as usize % CHUNK_SIZE
-                        + v.point.z as usize % CHUNK_SIZE * CHUNK_SIZE
-                        + v.point.y as usize % CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE] =
-                        color + color_info + 1;
-                }
-
-                output_model.voxels.push(ChunkData {
-                    pos: *c,
-                    data: new_data,
-                });
-            }
+            let output_model = model_from_vox(bytes, model_name)?;
 
             load_context.set_default_asset(LoadedAsset::new(output_model));
 
@@ -0,0 +1,685 @@
+// Models that fall, tumble and knock into each other. `drop <model> [speed]` spawns one in front of
+// the camera, and `clear_bodies` removes them all.
+//
+// Each physics step, the bodies that are moving are moved, lifted out of the world, and drawn back
+// in as Dynamic world updates, so that the shaders see them like any other voxels. Bodies only ever
+// draw into air, so lifting one out just means setting the voxels it drew back to air. The voxels
+// bodies drew are told apart from the static world by DynamicCells.
+//
+// Collisions are found one voxel at a time: every voxel on the surface of a body that would end up
+// inside of something solid is a contact, pushed out along the direction the solid voxel's open
+// faces point. Bodies that stop moving for a while fall asleep, and are left alone until something
+// hits them, so that a pile of still bodies doesn't keep re-uploading the world.
+
+use bevy::core::FixedTimestep;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::debug::console::ConsoleMessage;
+use crate::debug::registry::{AddCommands, ArgKind, ArgSpec, CommandSpec};
+use crate::debug::Command;
+use crate::rendering::camera::Camera;
+use crate::world::draw_type::{Dynamic, ModelType};
+use crate::world::model_loader::Model;
+use crate::world::model_type::ModelHolder;
+use crate::world::voxel_world::{is_air, VoxelWorld};
+use crate::world::{
+    queue_voxel, queued_voxel_at, ClearWorld, WorldUpdates, CHANGE_WORLD, CHUNK_SIZE,
+};
+use crate::PHYSICS_TIME_STEP;
+
+/// In voxels per second per second, the same as the player's default.
+const GRAVITY: f32 = 60.0;
+/// Every voxel weighs the same, so a body's mass is how many voxels it has.
+const VOXEL_MASS: f32 = 1.0;
+/// How much of the speed going into a contact bounces back out.
+const RESTITUTION: f32 = 0.2;
+const FRICTION: f32 = 0.6;
+/// How many times the contacts are gone over each step. More is steadier, but slower.
+const SOLVER_ITERATIONS: usize = 4;
+/// Spinning slows down by this much every step, so that bodies settle.
+const ANGULAR_DAMPING: f32 = 0.98;
+/// Faster than this, a body would skip through a voxel in one step.
+const MAX_SPEED: f32 = 50.0;
+/// Bodies slower than this, for SLEEP_STEPS steps in a row, fall asleep.
+const SLEEP_SPEED: f32 = 0.5;
+const SLEEP_STEPS: u32 = 30;
+/// How far bodies stuck inside of something are pushed out at a time, and how many times.
+const PUSH_OUT_STEP: f32 = 0.25;
+const PUSH_OUT_TRIES: usize = 8;
+/// How far in front of the camera dropped models are spawned.
+const DROP_DISTANCE: f32 = 24.0;
+
+/// The six neighbours of a voxel.
+const DIRECTIONS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [0, 1, 0],
+    [0, 0, 1],
+    [-1, 0, 0],
+    [0, -1, 0],
+    [0, 0, -1],
+];
+
+#[derive(Default)]
+pub struct RigidBodyPlugin;
+
+impl Plugin for RigidBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DynamicCells::default())
+            .insert_resource(WorldCleared::default())
+            .add_system(body_commands)
+            .add_system(create_bodies)
+            .add_system(forget_drawn_voxels.label(FORGET_DRAWN))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::step(PHYSICS_TIME_STEP))
+                    .with_system(step_bodies.label(CHANGE_WORLD).after(FORGET_DRAWN)),
+            )
+            .add_commands(COMMANDS);
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "drop",
+        description: "spawns a model from assets/models in front of the camera, that falls.",
+        args: &[
+            ArgSpec::new(
+                "model",
+                ArgKind::Asset {
+                    dir: "assets/models",
+                    extension: "vox",
+                },
+            ),
+            ArgSpec::new("speed", ArgKind::Float).optional(),
+        ],
+    },
+    CommandSpec {
+        name: "clear_bodies",
+        description: "removes every model spawned with drop.",
+        args: &[],
+    },
+];
+
+/// Whether the world is being cleared this frame. Set every frame by forget_drawn_voxels, since
+/// ClearWorld events can be gone by the time the next physics step runs.
+#[derive(Default)]
+struct WorldCleared(bool);
+
+/// Label for forget_drawn_voxels, which has to run before step_bodies.
+const FORGET_DRAWN: &str = "forget_drawn";
+
+/// Which body drew each voxel that bodies have drawn into the world.
+#[derive(Default)]
+pub struct DynamicCells(HashMap<IVec3, Entity>);
+
+impl DynamicCells {
+    /// Whether a body drew the voxel at pos, instead of it being part of the static world.
+    pub fn contains(&self, pos: IVec3) -> bool {
+        self.0.contains_key(&pos)
+    }
+
+    /// The body that drew the voxel at pos, if one did.
+    pub fn body_at(&self, pos: IVec3) -> Option<Entity> {
+        self.0.get(&pos).copied()
+    }
+}
+
+#[derive(Component)]
+pub struct RigidBody {
+    /// Where the center of mass is in the world.
+    pub position: Vec3,
+    pub orientation: Quat,
+    pub velocity: Vec3,
+    /// The axis it is spinning around, as long as how many radians per second it is spinning.
+    pub angular_velocity: Vec3,
+    pub mass: f32,
+    /// The inverse of the inertia tensor, in the body's own space.
+    inverse_inertia: Mat3,
+    /// The model's voxels, by their position in the model.
+    voxels: HashMap<IVec3, u32>,
+    /// Where the center of mass is in the model.
+    center: Vec3,
+    /// The middle of each voxel that has an open face, from the center of mass. Only these can
+    /// touch anything.
+    surface: Vec<Vec3>,
+    /// How far the furthest voxel is from the center of mass.
+    radius: f32,
+    /// The voxels this body drew into the world last, and where.
+    drawn: Vec<(IVec3, u32)>,
+    still_steps: u32,
+    pub asleep: bool,
+}
+
+impl RigidBody {
+    /// Returns None for models without any solid voxels.
+    pub fn from_model(model: &Model, position: Vec3, velocity: Vec3) -> Option<Self> {
+        let voxels: HashMap<IVec3, u32> = model
+            .voxels
+            .iter()
+            .flat_map(|chunk| {
+                let chunk_origin = chunk.pos.as_ivec3() * CHUNK_SIZE as i32;
+
+                chunk.data.iter().enumerate().map(move |(index, voxel)| {
+                    let pos_in_chunk = IVec3::new(
+                        (index % CHUNK_SIZE) as i32,
+                        (index / CHUNK_SIZE % CHUNK_SIZE) as i32,
+                        (index / CHUNK_SIZE / CHUNK_SIZE) as i32,
+                    );
+
+                    (chunk_origin + pos_in_chunk, *voxel)
+                })
+            })
+            .filter(|(_, voxel)| !is_air(*voxel))
+            .collect();
+
+        let mut body = RigidBody::from_voxels(voxels)?;
+        body.position = position;
+        body.velocity = velocity;
+
+        Some(body)
+    }
+
+    /// A body made of the given voxels, which starts out right where they are. Returns None if
+    /// there aren't any.
+    pub fn from_voxels(voxels: HashMap<IVec3, u32>) -> Option<Self> {
+        if voxels.is_empty() {
+            return None;
+        }
+
+        let middle = |pos: &IVec3| pos.as_vec3() + Vec3::splat(0.5);
+
+        let mass = voxels.len() as f32 * VOXEL_MASS;
+        let center = voxels.keys().map(middle).sum::<Vec3>() / voxels.len() as f32;
+
+        // each voxel is a small cube, which has an inertia of a sixth of its mass on its own. The
+        // off diagonal parts matter for bodies that aren't lined up with their axes, like an L.
+        let inertia = voxels.keys().fold(Mat3::ZERO, |inertia, pos| {
+            let r = middle(pos) - center;
+            let outer = Mat3::from_cols(r * r.x, r * r.y, r * r.z);
+
+            inertia + Mat3::from_diagonal(Vec3::splat(r.dot(r) + 1.0 / 6.0)) - outer
+        }) * VOXEL_MASS;
+
+        let surface = voxels
+            .keys()
+            .filter(|pos| {
+                DIRECTIONS
+                    .iter()
+                    .any(|dir| !voxels.contains_key(&(**pos + IVec3::from(*dir))))
+            })
+            .map(|pos| middle(pos) - center)
+            .collect();
+
+        let radius = voxels
+            .keys()
+            .map(|pos| (middle(pos) - center).length())
+            .fold(0.0, f32::max);
+
+        Some(RigidBody {
+            position: center,
+            orientation: Quat::IDENTITY,
+            velocity: Vec3::ZERO,
+            angular_velocity: Vec3::ZERO,
+            mass,
+            inverse_inertia: inertia.inverse(),
+            voxels,
+            center,
+            surface,
+            radius,
+            drawn: Vec::new(),
+            still_steps: 0,
+            asleep: false,
+        })
+    }
+
+    fn inverse_mass(&self) -> f32 {
+        1.0 / self.mass
+    }
+
+    /// The inverse inertia applied to a vector in world space.
+    fn apply_inverse_inertia(&self, v: Vec3) -> Vec3 {
+        self.orientation * (self.inverse_inertia * (self.orientation.inverse() * v))
+    }
+
+    /// How fast a point on the body is moving. offset is from the center of mass, in world space.
+    fn velocity_at(&self, offset: Vec3) -> Vec3 {
+        self.velocity + self.angular_velocity.cross(offset)
+    }
+
+    /// Pushes the body at a point. offset is from the center of mass, in world space.
+    pub fn apply_impulse(&mut self, impulse: Vec3, offset: Vec3) {
+        self.velocity += impulse * self.inverse_mass();
+        self.angular_velocity += self.apply_inverse_inertia(offset.cross(impulse));
+    }
+
+    pub fn wake(&mut self) {
+        self.asleep = false;
+        self.still_steps = 0;
+    }
+
+    /// Where the body would be after a step at its current speed.
+    fn moved(&self, dt: f32) -> (Vec3, Quat) {
+        let spin = self.angular_velocity * dt;
+        let orientation = if spin.length_squared() > 0.0 {
+            (Quat::from_scaled_axis(spin) * self.orientation).normalize()
+        } else {
+            self.orientation
+        };
+
+        (self.position + self.velocity * dt, orientation)
+    }
+
+    /// The solid voxels the body's surface would be inside of, at a position and orientation.
+    fn contacts(
+        &self,
+        position: Vec3,
+        orientation: Quat,
+        solid_at: impl Fn(IVec3) -> Option<Solid>,
+    ) -> Vec<Contact> {
+        self.surface
+            .iter()
+            .filter_map(|voxel| {
+                let offset = orientation * *voxel;
+                let cell = (position + offset).floor().as_ivec3();
+                let solid = solid_at(cell)?;
+
+                // the open faces of the voxel that was hit point the way out of it.
+                let normal = DIRECTIONS
+                    .iter()
+                    .map(|dir| IVec3::from(*dir))
+                    .filter(|dir| solid_at(cell + *dir) != Some(solid))
+                    .map(|dir| dir.as_vec3())
+                    .sum::<Vec3>()
+                    .try_normalize()
+                    .unwrap_or(Vec3::Y);
+
+                Some(Contact {
+                    offset,
+                    normal,
+                    solid,
+                })
+            })
+            .collect()
+    }
+}
+
+/// What a contact is against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Solid {
+    World,
+    Body(Entity),
+}
+
+struct Contact {
+    /// From the center of mass, in world space.
+    offset: Vec3,
+    normal: Vec3,
+    solid: Solid,
+}
+
+/// How fast another body was going when the step started, which its contacts push against.
+#[derive(Clone, Copy)]
+struct OtherBody {
+    position: Vec3,
+    velocity: Vec3,
+    angular_velocity: Vec3,
+    inverse_mass: f32,
+    asleep: bool,
+}
+
+/// `drop <model> [speed]` and `clear_bodies`.
+#[allow(clippy::too_many_arguments)]
+fn body_commands(
+    mut debug_commands: EventReader<Command>,
+    mut console: EventWriter<ConsoleMessage>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    camera: Res<Camera>,
+    bodies: Query<(Entity, &RigidBody)>,
+    unloaded: Query<Entity, (With<Dynamic>, Without<RigidBody>)>,
+    mut cells: ResMut<DynamicCells>,
+    mut world_updates: ResMut<WorldUpdates>,
+    voxel_world: Res<VoxelWorld>,
+) {
+    for cmd in debug_commands.iter() {
+        let result = match cmd.function.as_str() {
+            "drop" => {
+                let speed = if cmd.get_arg(1).is_empty() {
+                    Ok(0.0)
+                } else {
+                    cmd.parse_arg::<f32>(1)
+                };
+
+                speed.map(|speed| {
+                    let model = asset_server.load(&*format!("models/{}.vox", cmd.get_arg(0)));
+                    let position = camera.position + camera.forward() * DROP_DISTANCE;
+
+                    commands
+                        .spawn()
+                        .insert(ModelHolder::new_static(model))
+                        .insert(Transform::from_translation(position))
+                        .insert(Dynamic {
+                            velocity: camera.forward() * speed,
+                        });
+                })
+            }
+            "clear_bodies" => {
+                for (entity, body) in bodies.iter() {
+                    erase(entity, body, &mut cells, &mut world_updates, &voxel_world);
+                    commands.entity(entity).despawn();
+                }
+
+                for entity in unloaded.iter() {
+                    commands.entity(entity).despawn();
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            console.send(cmd.error(err));
+        }
+    }
+}
+
+/// Turns Dynamic models into rigid bodies once they have loaded.
+fn create_bodies(
+    mut commands: Commands,
+    mut console: EventWriter<ConsoleMessage>,
+    models: Res<Assets<Model>>,
+    new_bodies: Query<(Entity, &ModelHolder, &Transform, &Dynamic), Without<RigidBody>>,
+) {
+    for (entity, model_holder, transform, dynamic) in new_bodies.iter() {
+        let model = match models.get(model_holder.handle()) {
+            Some(model) => model,
+            None => continue,
+        };
+
+        match RigidBody::from_model(model, transform.translation, dynamic.velocity) {
+            Some(body) => {
+                commands.entity(entity).insert(body);
+            }
+            None => {
+                console.send(ConsoleMessage::error(
+                    "drop: the model doesn't have any voxels.",
+                ));
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+/// Sets the voxels a body drew back to air. Voxels that have been taken over since, by another
+/// body or by an edit, are left alone.
+fn erase(
+    entity: Entity,
+    body: &RigidBody,
+    cells: &mut DynamicCells,
+    world_updates: &mut WorldUpdates,
+    voxel_world: &VoxelWorld,
+) {
+    for (pos, voxel) in body.drawn.iter() {
+        if cells.0.get(pos) != Some(&entity)
+            || queued_voxel_at(world_updates, voxel_world, *pos) != *voxel
+        {
+            continue;
+        }
+
+        cells.0.remove(pos);
+        queue_voxel(world_updates, voxel_world, *pos, 0, ModelType::Dynamic);
+    }
+}
+
+/// Clearing the world takes the bodies with it, so they all have to be drawn again. Runs every
+/// frame, so that no ClearWorld is missed.
+fn forget_drawn_voxels(
+    mut clear_world: EventReader<ClearWorld>,
+    mut world_cleared: ResMut<WorldCleared>,
+    mut cells: ResMut<DynamicCells>,
+    mut bodies: Query<&mut RigidBody>,
+) {
+    world_cleared.0 = clear_world.iter().count() != 0;
+
+    if !world_cleared.0 {
+        return;
+    }
+
+    cells.0.clear();
+
+    for mut body in bodies.iter_mut() {
+        body.drawn.clear();
+        body.wake();
+    }
+}
+
+fn step_bodies(
+    mut bodies: Query<(Entity, &mut RigidBody, &mut Transform)>,
+    mut cells: ResMut<DynamicCells>,
+    mut world_updates: ResMut<WorldUpdates>,
+    voxel_world: Res<VoxelWorld>,
+    world_cleared: Res<WorldCleared>,
+) {
+    let dt = PHYSICS_TIME_STEP as f32;
+
+    // drawing waits for the next step, once the world has actually been cleared, or the chunks
+    // drawn into would be copied from the world from before the clear.
+    if world_cleared.0 {
+        return;
+    }
+
+    // bodies woken up by a knock this step haven't moved yet, so only these are drawn again.
+    let moving: HashSet<Entity> = bodies
+        .iter()
+        .filter(|(_, body, _)| !body.asleep)
+        .map(|(entity, _, _)| entity)
+        .collect();
+
+    let others: HashMap<Entity, OtherBody> = bodies
+        .iter()
+        .map(|(entity, body, _)| {
+            let other = OtherBody {
+                position: body.position,
+                velocity: body.velocity,
+                angular_velocity: body.angular_velocity,
+                inverse_mass: body.inverse_mass(),
+                asleep: body.asleep,
+            };
+
+            (entity, other)
+        })
+        .collect();
+
+    // impulses from bodies hitting sleeping ones, given to the body that was hit after every body
+    // has moved. Bodies that are awake push back on their own, from their side of the contact.
+    let mut knocks: Vec<(Entity, Vec3, Vec3)> = Vec::new();
+
+    for (entity, mut body, _) in bodies.iter_mut() {
+        if body.asleep {
+            continue;
+        }
+
+        let solid_at = |pos: IVec3| match cells.0.get(&pos) {
+            Some(other) if *other == entity => None,
+            Some(other) => Some(Solid::Body(*other)),
+            None if pos.y < 0 => Some(Solid::World),
+            None if !is_air(queued_voxel_at(&world_updates, &voxel_world, pos)) => {
+                Some(Solid::World)
+            }
+            None => None,
+        };
+
+        body.velocity.y -= GRAVITY * dt;
+
+        let (position, orientation) = body.moved(dt);
+        let contacts = body.contacts(position, orientation, &solid_at);
+
+        for iteration in 0..SOLVER_ITERATIONS {
+            for contact in contacts.iter() {
+                let other = match contact.solid {
+                    Solid::Body(other) => others.get(&other).copied(),
+                    Solid::World => None,
+                };
+
+                let other_velocity = other.map_or(Vec3::ZERO, |other| {
+                    let other_offset = body.position + contact.offset - other.position;
+
+                    other.velocity + other.angular_velocity.cross(other_offset)
+                });
+                let other_inverse_mass = other.map_or(0.0, |other| other.inverse_mass);
+
+                let relative = body.velocity_at(contact.offset) - other_velocity;
+                let into = relative.dot(contact.normal);
+
+                if into >= 0.0 {
+                    continue;
+                }
+
+                let effective_mass = |direction: Vec3| {
+                    let spin = body.apply_inverse_inertia(contact.offset.cross(direction));
+
+                    body.inverse_mass()
+                        + other_inverse_mass
+                        + direction.dot(spin.cross(contact.offset))
+                };
+
+                // bouncing only happens once, the other iterations just stop what's left.
+                let bounce = if iteration == 0 { RESTITUTION } else { 0.0 };
+                let push = -(1.0 + bounce) * into / effective_mass(contact.normal);
+                let mut impulse = contact.normal * push;
+
+                let sliding = relative - contact.normal * into;
+
+                if let Some(direction) = sliding.try_normalize() {
+                    let friction =
+                        (sliding.length() / effective_mass(direction)).min(FRICTION * push);
+
+                    impulse -= direction * friction;
+                }
+
+                body.apply_impulse(impulse, contact.offset);
+
+                if let (Solid::Body(hit), Some(other)) = (contact.solid, other) {
+                    if other.asleep {
+                        knocks.push((hit, -impulse, body.position + contact.offset));
+                    }
+                }
+            }
+        }
+
+        body.velocity = body.velocity.clamp_length_max(MAX_SPEED);
+        body.angular_velocity *= ANGULAR_DAMPING;
+
+        let (mut position, orientation) = body.moved(dt);
+
+        // anything still stuck inside of something after that is pushed straight out.
+        for _ in 0..PUSH_OUT_TRIES {
+            let stuck = body.contacts(position, orientation, &solid_at);
+
+            if stuck.is_empty() {
+                break;
+            }
+
+            let out = stuck
+                .iter()
+                .map(|contact| contact.normal)
+                .sum::<Vec3>()
+                .try_normalize()
+                .unwrap_or(Vec3::Y);
+
+            position += out * PUSH_OUT_STEP;
+        }
+
+        body.position = position;
+        body.orientation = orientation;
+
+        let still = body.velocity.length() < SLEEP_SPEED
+            && body.angular_velocity.length() < SLEEP_SPEED / body.radius.max(1.0);
+
+        body.still_steps = if still { body.still_steps + 1 } else { 0 };
+
+        if body.still_steps >= SLEEP_STEPS {
+            body.asleep = true;
+            body.velocity = Vec3::ZERO;
+            body.angular_velocity = Vec3::ZERO;
+        }
+    }
+
+    for (other, impulse, point) in knocks {
+        if let Ok((_, mut body, _)) = bodies.get_mut(other) {
+            let offset = point - body.position;
+
+            body.apply_impulse(impulse, offset);
+            body.wake();
+        }
+    }
+
+    // every body that moved is lifted out before any are drawn back, so that they don't get in
+    // each other's way.
+    for (entity, mut body, _) in bodies.iter_mut() {
+        if moving.contains(&entity) {
+            erase(entity, &body, &mut cells, &mut world_updates, &voxel_world);
+            body.drawn.clear();
+        }
+    }
+
+    for (entity, mut body, mut transform) in bodies.iter_mut() {
+        if !moving.contains(&entity) {
+            continue;
+        }
+
+        draw(
+            entity,
+            &mut body,
+            &mut cells,
+            &mut world_updates,
+            &voxel_world,
+        );
+
+        transform.translation = body.position;
+        transform.rotation = body.orientation;
+    }
+}
+
+/// Draws a body into the air around it. Each voxel of the world it covers is looked up in the
+/// model, so that turned bodies don't have gaps in them.
+fn draw(
+    entity: Entity,
+    body: &mut RigidBody,
+    cells: &mut DynamicCells,
+    world_updates: &mut WorldUpdates,
+    voxel_world: &VoxelWorld,
+) {
+    let reach = Vec3::splat(body.radius + 1.0);
+    let min = (body.position - reach).floor().as_ivec3();
+    let max = (body.position + reach).ceil().as_ivec3();
+    let to_model = body.orientation.inverse();
+
+    for z in min.z..max.z {
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let pos = IVec3::new(x, y, z);
+                let in_model =
+                    to_model * (pos.as_vec3() + Vec3::splat(0.5) - body.position) + body.center;
+
+                let voxel = match body.voxels.get(&in_model.floor().as_ivec3()) {
+                    Some(voxel) => *voxel,
+                    None => continue,
+                };
+
+                if !VoxelWorld::in_bounds(pos)
+                    || cells.0.contains_key(&pos)
+                    || !is_air(queued_voxel_at(world_updates, voxel_world, pos))
+                {
+                    continue;
+                }
+
+                queue_voxel(world_updates, voxel_world, pos, voxel, ModelType::Dynamic);
+                cells.0.insert(pos, entity);
+                body.drawn.push((pos, voxel));
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+
+use crate::world::{
+    chunk_position_to_index, ChunkData, CHUNKS_X, CHUNKS_Y, CHUNKS_Z, CHUNK_COUNT, CHUNK_SIZE,
+    CHUNK_VOL, VOXEL_COUNT, WORLD_SIZE_X, WORLD_SIZE_Y, WORLD_SIZE_Z,
+};
+
+/// A copy of the world buffer that lives on the cpu. It is laid out exactly like the buffer the
+/// shaders read from, and is kept in sync with it by update_world, so that editing tools can look
+/// at voxels without reading anything back from the gpu.
+pub struct VoxelWorld {
+    pub voxels: Vec<u32>,
+    pub filled_chunks: [bool; CHUNK_COUNT],
+}
+
+impl Default for VoxelWorld {
+    fn default() -> Self {
+        VoxelWorld {
+            voxels: vec![0; VOXEL_COUNT],
+            filled_chunks: [false; CHUNK_COUNT],
+        }
+    }
+}
+
+impl VoxelWorld {
+    pub fn in_bounds(pos: IVec3) -> bool {
+        pos.x >= 0
+            && pos.y >= 0
+            && pos.z >= 0
+            && (pos.x as usize) < WORLD_SIZE_X
+            && (pos.y as usize) < WORLD_SIZE_Y
+            && (pos.z as usize) < WORLD_SIZE_Z
+    }
+
+    /// Matches voxel_unit_at in index_world.glsl.
+    pub fn voxel_index(pos: UVec3) -> usize {
+        let chunk_pos = pos / CHUNK_SIZE as u32;
+        let pos_in_chunk = pos % CHUNK_SIZE as u32;
+
+        chunk_position_to_index(chunk_pos) * CHUNK_VOL
+            + pos_in_chunk.x as usize
+            + pos_in_chunk.y as usize * CHUNK_SIZE
+            + pos_in_chunk.z as usize * CHUNK_SIZE * CHUNK_SIZE
+    }
+
+    /// Returns 0 (air) for anything outside of the world, like the shader does.
+    pub fn voxel_at(&self, pos: IVec3) -> u32 {
+        if !Self::in_bounds(pos) {
+            return 0;
+        }
+
+        self.voxels[Self::voxel_index(pos.as_uvec3())]
+    }
+
+    pub fn set_voxel(&mut self, pos: IVec3, voxel: u32) {
+        if !Self::in_bounds(pos) {
+            return;
+        }
+
+        let pos = pos.as_uvec3();
+        self.voxels[Self::voxel_index(pos)] = voxel;
+        self.filled_chunks[chunk_position_to_index(pos / CHUNK_SIZE as u32)] = true;
+    }
+
+    pub fn is_chunk_filled_at(&self, chunk_pos: IVec3) -> bool {
+        if chunk_pos.x < 0
+            || chunk_pos.y < 0
+            || chunk_pos.z < 0
+            || chunk_pos.x as usize >= CHUNKS_X
+            || chunk_pos.y as usize >= CHUNKS_Y
+            || chunk_pos.z as usize >= CHUNKS_Z
+        {
+            return false;
+        }
+
+        self.filled_chunks[chunk_position_to_index(chunk_pos.as_uvec3())]
+    }
+
+    pub fn chunk(&self, chunk_pos: UVec3) -> [u32; CHUNK_VOL] {
+        let start = chunk_position_to_index(chunk_pos) * CHUNK_VOL;
+        let mut data = [0; CHUNK_VOL];
+        data.copy_from_slice(&self.voxels[start..start + CHUNK_VOL]);
+        data
+    }
+
+    pub fn set_chunk(&mut self, chunk_pos: UVec3, data: &[u32; CHUNK_VOL]) {
+        let chunk_index = chunk_position_to_index(chunk_pos);
+        let start = chunk_index * CHUNK_VOL;
+
+        self.voxels[start..start + CHUNK_VOL].copy_from_slice(data);
+        self.filled_chunks[chunk_index] = true;
+    }
+
+    /// Writes chunks straight into the world, the same way update_world does with WorldUpdates.
+    pub fn draw(&mut self, chunks: &[ChunkData]) {
+        for chunk in chunks {
+            self.set_chunk(chunk.pos, &chunk.data);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for voxel in self.voxels.iter_mut() {
+            *voxel = 0;
+        }
+
+        self.filled_chunks = [false; CHUNK_COUNT];
+    }
+}
+
+/// Matches is_air in extract_color.glsl.
+pub fn is_air(voxel: u32) -> bool {
+    voxel & 1 == 0
+}